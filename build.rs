@@ -5,9 +5,7 @@
 
 use clap::{CommandFactory, ValueEnum};
 use clap_complete::{Shell, generate_to};
-use clap_mangen::Man;
 use std::env;
-use std::fs::File;
 use std::io::Error;
 
 include!("src/cli.rs");
@@ -28,10 +26,7 @@ fn main() -> Result<(), Error> {
         generate_to(shell, &mut cmd, "aksync", &outdir)?;
     }
 
-    let file = PathBuf::from(&outdir).join("aksync.1");
-    let mut file = File::create(file)?;
-
-    Man::new(cmd).render(&mut file)?;
+    clap_mangen::generate_to(Cli::command(), &outdir)?;
 
     println!("cargo:info=completion files & manpages are generated: {outdir:?}");
 
@@ -80,7 +80,13 @@
           toolchain = pkgs.rust-bin.fromRustupToolchainFile ./rust-toolchain.toml;
 
           crane = (inputs.crane.mkLib pkgs).overrideToolchain toolchain;
-          src = crane.cleanCargoSource ./.;
+          src = lib.fileset.toSource {
+            root = ./.;
+            fileset = lib.fileset.unions [
+              (crane.fileset.commonCargoSources ./.)
+              ./templates
+            ];
+          };
 
           commonArgs = {
             inherit src;
@@ -116,6 +122,7 @@
                 ./Cargo.toml
                 ./Cargo.lock
                 ./build.rs
+                ./templates
                 crate
               ];
             };
@@ -132,7 +139,7 @@
               MAN_OUT = "/build";
 
               preInstall = ''
-                installManPage $MAN_OUT/aksync.1
+                installManPage $MAN_OUT/aksync*.1
                 installShellCompletion \
                   --fish $MAN_OUT/aksync.fish \
                   --bash $MAN_OUT/aksync.bash \
@@ -16,6 +16,8 @@ let
     mkOption
     types
     ;
+
+  settingsFormat = pkgs.formats.toml { };
 in
 {
   options.die-koma.aksync = {
@@ -23,21 +25,102 @@ in
 
     passwordFile = mkOption {
       description = "File containing the KoMapedia bot password";
-      type = types.path;
+      type = types.nullOr types.path;
+      default = null;
+    };
+
+    oauthTokenFile = mkOption {
+      description = ''
+        File containing the access token of an OAuth 2.0 owner-only consumer
+        for KoMapedia, used instead of the bot password if set
+      '';
+      type = types.nullOr types.path;
+      default = null;
+    };
+
+    aktoolTokenFile = mkOption {
+      description = ''
+        File containing an API token for aktool, giving access to data
+        that is only available to authenticated users
+      '';
+      type = types.nullOr types.path;
+      default = null;
+    };
+
+    aktoolAuthentication = mkOption {
+      description = "How the secret in `aktoolTokenFile` is sent to aktool";
+      type = types.enum [
+        "token"
+        "session"
+      ];
+      default = "token";
+    };
+
+    smtpPasswordFile = mkOption {
+      description = ''
+        File containing the password for `settings.notify.email.username`
+        on the SMTP server used for notifications
+      '';
+      type = types.nullOr types.path;
+      default = null;
     };
 
     onCalendar = mkOption {
-      description = "When to run aksync";
+      description = "When to run aksync, unless running as a daemon";
       type = types.listOf types.str;
+      default = [ ];
+    };
+
+    serve = mkEnableOption ''
+      running aksync as a daemon that synchronises whenever it receives a
+      POST request to `/sync` on the address given in `settings.serve.listen`
+    '';
+
+    settings = mkOption {
+      description = "Configuration for aksync, see `src/config.rs` for the available settings";
+      type = settingsFormat.type;
+      default = { };
+      example = {
+        komapedia = {
+          maxlag = 5;
+          retry.max-retries = 10;
+        };
+      };
     };
   };
 
   config =
     let
       cfg = config.die-koma.aksync;
+      configFile = settingsFormat.generate "aksync.toml" cfg.settings;
     in
     mkIf cfg.enable {
 
+      assertions = [
+        {
+          assertion = (cfg.passwordFile == null) != (cfg.oauthTokenFile == null);
+          message = "die-koma.aksync: exactly one of passwordFile and oauthTokenFile must be set";
+        }
+        {
+          assertion = cfg.serve || cfg.onCalendar != [ ];
+          message = "die-koma.aksync: onCalendar must be set unless serve is enabled";
+        }
+      ];
+
+      die-koma.aksync.settings = {
+        lock-file = lib.mkDefault "/run/aksync/lock";
+        state-file = lib.mkDefault "/var/lib/aksync/state.json";
+        aktool = {
+          cache-dir = lib.mkDefault "/var/cache/aksync";
+          authentication = lib.mkDefault (
+            if cfg.aktoolTokenFile != null then cfg.aktoolAuthentication else "none"
+          );
+        };
+        komapedia.authentication = lib.mkDefault (
+          if cfg.oauthTokenFile != null then "oauth" else "bot-password"
+        );
+      };
+
       systemd = {
         services.aksync = {
           after = [
@@ -46,20 +129,31 @@ in
             "phpfpm-mediawiki.service"
           ];
           reloadTriggers = [ ];
+          wantedBy = lib.optional cfg.serve "multi-user.target";
           serviceConfig = {
             DynamicUser = true;
-            ExecStart = "${lib.getExe pkgs.aksync}";
-            Type = "oneshot";
-            LoadCredential = [
-              "aksync-bot-password:${cfg.passwordFile}"
-            ];
-            Environment = [
-              "AKSYNC_BOT_PASSWORD_FILE=%d/aksync-bot-password"
-            ];
+            ExecStart = "${lib.getExe pkgs.aksync} --config ${configFile} ${
+              if cfg.serve then "serve" else "sync"
+            }";
+            Type = if cfg.serve then "simple" else "oneshot";
+            Restart = lib.mkIf cfg.serve "on-failure";
+            CacheDirectory = "aksync";
+            RuntimeDirectory = "aksync";
+            StateDirectory = "aksync";
+            LoadCredential =
+              lib.optional (cfg.passwordFile != null) "aksync-bot-password:${cfg.passwordFile}"
+              ++ lib.optional (cfg.oauthTokenFile != null) "aksync-oauth-token:${cfg.oauthTokenFile}"
+              ++ lib.optional (cfg.aktoolTokenFile != null) "aksync-aktool-token:${cfg.aktoolTokenFile}"
+              ++ lib.optional (cfg.smtpPasswordFile != null) "aksync-smtp-password:${cfg.smtpPasswordFile}";
+            Environment =
+              lib.optional (cfg.passwordFile != null) "AKSYNC_BOT_PASSWORD_FILE=%d/aksync-bot-password"
+              ++ lib.optional (cfg.oauthTokenFile != null) "AKSYNC_OAUTH_TOKEN_FILE=%d/aksync-oauth-token"
+              ++ lib.optional (cfg.aktoolTokenFile != null) "AKSYNC_AKTOOL_TOKEN_FILE=%d/aksync-aktool-token"
+              ++ lib.optional (cfg.smtpPasswordFile != null) "AKSYNC_SMTP_PASSWORD_FILE=%d/aksync-smtp-password";
           };
         };
 
-        timers.aksync = {
+        timers.aksync = mkIf (!cfg.serve) {
           timerConfig = {
             OnCalendar = cfg.onCalendar;
             Unit = "aksync.service";
@@ -6,25 +6,46 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use reqwest::{
-    Client, Response,
-    header::{HeaderMap, HeaderValue},
+    Client, StatusCode,
+    header::{
+        AUTHORIZATION, COOKIE, ETAG, HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+        LAST_MODIFIED,
+    },
 };
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
 use crate::{
     AKSYNC_USER_AGENT,
+    config::{AKToolAuthentication, AKToolConfig, secret_from_env},
+    http,
+    metrics::{METRICS, Service},
     model::{Event, EventId, aktool},
+    users::Users,
 };
 
 pub struct AKToolApi {
     client: Client,
     iri: String,
+    config: AKToolConfig,
 }
 
-#[derive(Debug)]
+/// A cached response, used to make conditional requests.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct CacheEntry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
+
+#[derive(Debug, Clone, Copy)]
 enum Endpoint {
     AK,
     Category,
@@ -54,44 +75,203 @@ impl Display for Endpoint {
 }
 
 impl AKToolApi {
-    pub fn new(iri: String) -> Result<Self> {
-        let headers = HeaderMap::from_iter([(
+    pub fn new(iri: String, config: &AKToolConfig) -> Result<Self> {
+        let mut headers = HeaderMap::from_iter([(
             reqwest::header::ACCEPT,
             HeaderValue::from_static("application/json"),
         )]);
+
+        let credentials = match config.authentication {
+            AKToolAuthentication::None => None,
+            AKToolAuthentication::Token => Some((
+                AUTHORIZATION,
+                format!("Token {}", secret_from_env("AKSYNC_AKTOOL_TOKEN")?),
+            )),
+            AKToolAuthentication::Session => Some((
+                COOKIE,
+                format!("sessionid={}", secret_from_env("AKSYNC_AKTOOL_TOKEN")?),
+            )),
+        };
+        if let Some((name, value)) = credentials {
+            let mut value = HeaderValue::from_str(&value)?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
         let client = Client::builder()
             .user_agent(AKSYNC_USER_AGENT)
             .default_headers(headers)
+            .timeout(Duration::from_secs(config.timeout))
             .build()?;
 
-        Ok(Self { client, iri })
-    }
-
-    async fn get(&self, endpoint: Endpoint) -> reqwest::Result<Response> {
-        self.client.get(endpoint.iri(self.iri.clone())).send().await
-    }
-
-    pub async fn events(&self) -> Result<HashMap<EventId, Event>> {
-        let categories = self
-            .get(Endpoint::Category)
-            .await?
-            .json::<Vec<aktool::Category>>()
-            .await?;
-        let owners = self
-            .get(Endpoint::Owner)
-            .await?
-            .json::<Vec<aktool::Owner>>()
-            .await?;
-        let aks = self
-            .get(Endpoint::AK)
-            .await?
-            .json::<Vec<aktool::AK>>()
-            .await?;
-        let slots = self
-            .get(Endpoint::Slot)
-            .await?
-            .json::<Vec<aktool::Slot>>()
-            .await?;
+        Ok(Self {
+            client,
+            iri,
+            config: config.clone(),
+        })
+    }
+
+    fn cache_file(&self, endpoint: &Endpoint) -> Option<PathBuf> {
+        self.config
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{endpoint}.json")))
+    }
+
+    fn cached(&self, endpoint: &Endpoint) -> Option<CacheEntry> {
+        let file = self.cache_file(endpoint)?;
+        let entry = fs::read_to_string(&file).ok()?;
+
+        serde_json::from_str(&entry)
+            .inspect_err(|err| log::warn!("ignoring invalid cache file {file:?}: {err}"))
+            .ok()
+    }
+
+    fn store(&self, endpoint: &Endpoint, entry: &CacheEntry) -> Result<()> {
+        if let Some(file) = self.cache_file(endpoint) {
+            if let Some(dir) = file.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&file, serde_json::to_string(entry)?)
+                .with_context(|| format!("failed to write cache file {file:?}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Fetch the body of `endpoint`, retrying on transient errors and
+    /// reusing the cached body if the endpoint has not changed.
+    async fn get(&self, endpoint: Endpoint) -> Result<String> {
+        let cached = self.cached(&endpoint);
+        let mut retry = 0;
+
+        loop {
+            let mut request = self.client.get(endpoint.iri(self.iri.clone()));
+            if let Some(entry) = &cached {
+                if let Some(etag) = &entry.etag {
+                    request = request.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = &entry.last_modified {
+                    request = request.header(IF_MODIFIED_SINCE, last_modified);
+                }
+            }
+
+            let start = Instant::now();
+            let response = request.send().await;
+            METRICS.request(
+                Service::AKTool,
+                start.elapsed(),
+                response.as_ref().is_ok_and(|response| {
+                    response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED
+                }),
+            );
+
+            let (reason, delay) = match response {
+                Ok(response) if response.status() == StatusCode::NOT_MODIFIED => {
+                    if let Some(entry) = cached {
+                        log::debug!("{endpoint} is unchanged, using cached response");
+                        return Ok(entry.body);
+                    }
+
+                    bail!("got HTTP status 304 without a cached response");
+                }
+                Ok(response) if http::is_transient(response.status()) => (
+                    format!("got HTTP status {}", response.status()),
+                    http::retry_after(response.headers()),
+                ),
+                Ok(response) if !response.status().is_success() => {
+                    bail!("got HTTP status {}", response.status())
+                }
+                Ok(response) => {
+                    let header = |name| {
+                        response
+                            .headers()
+                            .get(name)
+                            .and_then(|value: &HeaderValue| value.to_str().ok())
+                            .map(str::to_string)
+                    };
+                    let etag = header(ETAG);
+                    let last_modified = header(LAST_MODIFIED);
+                    let entry = CacheEntry {
+                        etag,
+                        last_modified,
+                        body: response.text().await?,
+                    };
+
+                    self.store(&endpoint, &entry)?;
+                    return Ok(entry.body);
+                }
+                Err(err) if err.is_timeout() || err.is_connect() => (err.to_string(), None),
+                Err(err) => return Err(err.into()),
+            };
+
+            http::backoff(&self.config.retry, retry, &reason, delay).await?;
+            retry += 1;
+        }
+    }
+
+    /// Fetch and parse the list of objects served by `endpoint`.
+    async fn fetch<T: DeserializeOwned>(&self, endpoint: Endpoint) -> Result<Vec<T>> {
+        let body = self
+            .get(endpoint)
+            .await
+            .with_context(|| format!("failed to fetch aktool endpoint {endpoint}"))?;
+
+        serde_json::from_str(&body)
+            .with_context(|| format!("invalid response from aktool endpoint {endpoint}"))
+    }
+
+    /// Fetch the raw data of all events.
+    pub async fn snapshot(&self) -> Result<Snapshot> {
+        let (categories, owners, aks, slots) = tokio::try_join!(
+            self.fetch::<aktool::Category>(Endpoint::Category),
+            self.fetch::<aktool::Owner>(Endpoint::Owner),
+            self.fetch::<aktool::AK>(Endpoint::AK),
+            self.fetch::<aktool::Slot>(Endpoint::Slot),
+        )?;
+
+        Ok(Snapshot {
+            categories,
+            owners,
+            aks,
+            slots,
+        })
+    }
+
+    pub async fn events(&self, users: &Users) -> Result<HashMap<EventId, Event>> {
+        self.snapshot().await?.events(users)
+    }
+}
+
+/// The raw data served by aktool, which can be stored to and loaded
+/// from a file instead of querying aktool.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Snapshot {
+    categories: Vec<aktool::Category>,
+    owners: Vec<aktool::Owner>,
+    aks: Vec<aktool::AK>,
+    slots: Vec<aktool::Slot>,
+}
+
+impl Snapshot {
+    pub fn load(path: &Path) -> Result<Self> {
+        serde_json::from_str(
+            &fs::read_to_string(path)
+                .with_context(|| format!("failed to read snapshot {path:?}"))?,
+        )
+        .with_context(|| format!("invalid snapshot {path:?}"))
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, self)?)
+    }
+
+    pub fn events(self, users: &Users) -> Result<HashMap<EventId, Event>> {
+        let Self {
+            categories,
+            owners,
+            aks,
+            slots,
+        } = self;
 
         let mut events = HashSet::new();
         let mut categories_by_event = HashMap::<_, Vec<_>>::new();
@@ -147,7 +327,7 @@ impl AKToolApi {
                     .ok_or(anyhow!("unknown event {id:?}"))?
                     .iter()
                     .cloned();
-                let mut event = Event::new(categories, owners);
+                let mut event = Event::new(categories, owners, users);
                 for ak in aks_by_event
                     .get(&id)
                     .ok_or(anyhow!("unknown event {id:?}"))?
@@ -167,3 +347,112 @@ impl AKToolApi {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        env, fs,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use axum::{
+        Router,
+        http::{HeaderMap, StatusCode, header},
+        response::IntoResponse,
+        routing::get,
+    };
+    use test_log::test;
+
+    use super::{AKToolApi, Endpoint, Snapshot};
+    use crate::{
+        config::AKToolConfig,
+        http::serve,
+        model::{AKId, aktool::EVENT_KOMA92},
+        users::Users,
+    };
+
+    #[test]
+    fn events_from_snapshot() {
+        let snapshot = serde_json::from_str::<Snapshot>(
+            r##"{
+  "categories": [{"id":64,"name":"Inhalt/Arbeit","color":"#487eb0","description":"","present_by_default":false,"event":16}],
+  "owners": [{"id":1312,"name":"mmarx","slug":"mmarx","institution":"TU Dresden","link":"https://de.komapedia.org/wiki/Benutzer:Mmarx","event":16}],
+  "aks": [{"id":1289,"name":"Testwursttesting","short_name":"Testwurst","description":"Wir testen das Verspeisen leckerer Testwürste","link":"","protocol_link":"","reso":true,"present":null,"notes":"","interest":-1,"interest_counter":0,"include_in_export":true,"category":64,"track":null,"event":16,"owners":[1312],"types":[1,2],"requirements":[],"conflicts":[],"prerequisites":[]}],
+  "slots": [{"id":1,"start":null,"duration":"2.00","fixed":false,"updated":"2025-05-01T12:00:00+02:00","ak":1289,"room":null,"event":16}]
+}"##,
+        )
+        .unwrap();
+
+        let events = snapshot.events(&Users::default()).unwrap();
+        let event = events.get(&EVENT_KOMA92).unwrap();
+        let aks = event.aks().map(|(id, _)| *id).collect::<Vec<_>>();
+
+        assert_eq!(aks, vec![AKId::new(1289)]);
+    }
+
+    #[test(tokio::test)]
+    async fn not_modified() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let app = Router::new().route(
+            "/ak/",
+            get(move |headers: HeaderMap| async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                match headers.get(header::IF_NONE_MATCH) {
+                    Some(etag) if etag == "\"v1\"" => StatusCode::NOT_MODIFIED.into_response(),
+                    _ => ([(header::ETAG, "\"v1\"")], "[]").into_response(),
+                }
+            }),
+        );
+
+        let cache_dir = env::temp_dir().join(format!("aksync-test-cache-{}", std::process::id()));
+        let config = AKToolConfig {
+            cache_dir: Some(cache_dir.clone()),
+            ..Default::default()
+        };
+        let api = AKToolApi::new(serve(app).await, &config).unwrap();
+
+        assert_eq!(api.get(Endpoint::AK).await.unwrap(), "[]");
+        assert_eq!(api.get(Endpoint::AK).await.unwrap(), "[]");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[test(tokio::test)]
+    async fn retry() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let app = Router::new().route(
+            "/ak/",
+            get(move || async move {
+                match counter.fetch_add(1, Ordering::SeqCst) {
+                    0 => {
+                        // longer than the timeout
+                        tokio::time::sleep(Duration::from_secs(3)).await;
+                        "[]".into_response()
+                    }
+                    1 => (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        [(header::RETRY_AFTER, "0")],
+                    )
+                        .into_response(),
+                    _ => "[1]".into_response(),
+                }
+            }),
+        );
+
+        let mut config = AKToolConfig {
+            timeout: 1,
+            ..Default::default()
+        };
+        config.retry.initial_delay = 0;
+        let api = AKToolApi::new(serve(app).await, &config).unwrap();
+
+        assert_eq!(api.get(Endpoint::AK).await.unwrap(), "[1]");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+}
@@ -3,11 +3,105 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 pub(crate) struct Cli {
-    config: Option<PathBuf>,
+    /// Path to the TOML configuration file
+    #[arg(short, long, global = true)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// Format of log messages
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub(crate) log_format: LogFormat,
+
+    /// What to do, defaults to `sync`
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub(crate) enum LogFormat {
+    /// Human-readable messages
+    #[default]
+    Text,
+    /// One JSON object per message, including structured fields
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Synchronise AKs from aktool to KoMapedia
+    Sync(SyncArgs),
+    /// Dump the aktool data as a JSON snapshot
+    Fetch {
+        /// Write the snapshot to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the wikitext generated for an AK, or write it to files
+    Render {
+        /// The aktool id of the AK, renders all AKs if omitted
+        #[arg(required_unless_present = "output_dir")]
+        ak: Option<u64>,
+
+        /// Write one `.wiki` file per page into this directory
+        #[arg(short, long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+
+        /// Also write an `index.wiki` listing all pages
+        #[arg(long, requires = "output_dir")]
+        index: bool,
+
+        #[command(flatten)]
+        source: SourceArgs,
+    },
+    /// Compare the generated wikitext with the pages on KoMapedia
+    Diff {
+        #[command(flatten)]
+        source: SourceArgs,
+    },
+    /// Delete pages of AKs that no longer exist in aktool
+    Prune {
+        #[command(flatten)]
+        source: SourceArgs,
+    },
+    /// Run as a daemon, synchronising on request via HTTP
+    Serve(SyncArgs),
+}
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct SyncArgs {
+    /// Only synchronise the AK with this aktool id
+    #[arg(long = "ak", value_name = "ID")]
+    pub(crate) aks: Vec<u64>,
+
+    /// Only synchronise AKs whose name, as given in aktool or with the
+    /// `AK ` prefix of the page title, matches this regular expression
+    #[arg(long = "name", value_name = "PATTERN")]
+    pub(crate) names: Vec<String>,
+
+    /// Only synchronise AKs in this category
+    #[arg(long = "category", value_name = "NAME")]
+    pub(crate) categories: Vec<String>,
+
+    /// Delete pages of AKs removed from aktool even if only some AKs are synchronised
+    #[arg(long)]
+    pub(crate) prune: bool,
+
+    /// Update pages even if they have not changed since the last run
+    #[arg(long)]
+    pub(crate) force: bool,
+
+    #[command(flatten)]
+    pub(crate) source: SourceArgs,
+}
+
+#[derive(Args, Debug, Default)]
+pub(crate) struct SourceArgs {
+    /// Read the aktool data from a snapshot created by `fetch`
+    #[arg(long)]
+    pub(crate) from_snapshot: Option<PathBuf>,
 }
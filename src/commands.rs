@@ -0,0 +1,271 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File, TryLockError},
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use itertools::Itertools;
+
+use crate::{
+    AKTOOL_ENDPOINT,
+    aktool::{AKToolApi, Snapshot},
+    cli::{SourceArgs, SyncArgs},
+    config::Config,
+    diff::unified_diff,
+    komapedia::{KoMapedia, escape, wikipage},
+    logging,
+    metrics::METRICS,
+    model::{AKId, Event, EventId, Selection},
+    notify::notify,
+    report::Report,
+    state::State,
+    template::Template,
+    users::Users,
+};
+
+fn aktool_api(config: &Config) -> Result<AKToolApi> {
+    AKToolApi::new(AKTOOL_ENDPOINT.to_string(), &config.aktool)
+}
+
+/// Obtain the events, either from aktool or from a snapshot.
+async fn events(config: &Config, source: &SourceArgs) -> Result<HashMap<EventId, Event>> {
+    let users = Users::load(config.komapedia.users.as_deref())?;
+    match &source.from_snapshot {
+        Some(path) => {
+            log::info!("reading aktool snapshot {path:?}");
+            Snapshot::load(path)?.events(&users)
+        }
+        None => {
+            log::info!("querying aktool");
+            aktool_api(config)?.events(&users).await
+        }
+    }
+}
+
+/// Lock the lock file, failing if another run holds the lock.
+fn lock(path: &Path) -> Result<File> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("failed to open lock file {path:?}"))?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => bail!("another aksync run holds the lock {path:?}"),
+        Err(TryLockError::Error(err)) => Err(err.into()),
+    }
+}
+
+pub(crate) async fn sync(config: &Config, args: &SyncArgs) -> Result<()> {
+    let _lock = config.lock_file.as_deref().map(lock).transpose()?;
+    logging::start_run();
+    let mut report = Report::default();
+    let result = async {
+        // seed the metric first, so a failed login does not look like
+        // we never succeeded
+        let state = State::load(config.state_file.as_deref())?.force(args.force);
+        if let Some(timestamp) = state.last_success() {
+            METRICS.last_success(timestamp);
+        }
+
+        let mut komapedia = KoMapedia::login(&config.komapedia).await?;
+        let result = sync_events(config, args, &mut komapedia, state, &mut report).await;
+        report.finish(&result);
+
+        if let Some(page) = &config.komapedia.log_page
+            && !report.is_empty()
+            && let Err(err) = komapedia.append_log(page, &report).await
+        {
+            log::error!("failed to update log page {page}: {err:#}");
+        }
+
+        result
+    }
+    .await;
+    report.finish(&result);
+
+    METRICS.run(result.is_ok());
+    if let Some(path) = &config.metrics_file
+        && let Err(err) = METRICS.write(path)
+    {
+        log::error!("{err:#}");
+    }
+
+    if let Err(err) = notify(&config.notify, &report).await {
+        log::error!("{err:#}");
+    }
+
+    result
+}
+
+async fn sync_events(
+    config: &Config,
+    args: &SyncArgs,
+    komapedia: &mut KoMapedia,
+    mut state: State,
+    report: &mut Report,
+) -> Result<()> {
+    let selection = Selection::new(&args.aks, &args.names, &args.categories)?;
+    let events = events(config, &args.source).await?;
+
+    for (id, ref event) in events {
+        let wikipage = wikipage(id)?;
+        log::info!(event = id, page = wikipage.as_str(); "processing event {id} ({wikipage})");
+        let result = komapedia
+            .update_event(id, event, &selection, &mut state, report)
+            .await;
+        // keep track of the AKs we did update, even if others failed
+        state.save()?;
+        result?;
+        log::info!(event = id, page = wikipage.as_str(); "updated AKs for KoMapedia page {wikipage}");
+
+        if selection.is_all() || args.prune {
+            komapedia
+                .delete_old_pages(id, event, &mut state, report)
+                .await?;
+            state.save()?;
+        }
+
+        if let Some(subpage) = &config.komapedia.owners_page {
+            komapedia.update_owners_page(id, event, subpage).await?;
+        }
+    }
+
+    state.succeeded();
+    state.save()
+}
+
+pub(crate) async fn fetch(config: &Config, output: Option<PathBuf>) -> Result<()> {
+    log::info!("querying aktool");
+    let snapshot = aktool_api(config)?.snapshot().await?;
+
+    match output {
+        Some(path) => snapshot.write(File::create(path)?),
+        None => snapshot.write(io::stdout().lock()),
+    }
+}
+
+pub(crate) async fn render(
+    config: &Config,
+    ak: Option<u64>,
+    output_dir: Option<PathBuf>,
+    index: bool,
+    source: SourceArgs,
+) -> Result<()> {
+    let events = events(config, &source).await?;
+    let template = Template::load(&config.komapedia)?;
+    let selected = ak.map(AKId::new);
+
+    let Some(dir) = output_dir else {
+        let id = selected.ok_or_else(|| anyhow!("no AK given"))?;
+        let ak = events
+            .values()
+            .find_map(|event| event.ak(&id))
+            .ok_or_else(|| anyhow!("unknown AK {id}"))?;
+
+        print!("{}", template.render(ak)?);
+        return Ok(());
+    };
+
+    let mut pages = Vec::new();
+    for (&id, event) in events.iter().sorted_by_key(|&(id, _)| id) {
+        for (ak_id, ak) in event.aks() {
+            if !ak.is_koma() || selected.is_some_and(|selected| selected != *ak_id) {
+                continue;
+            }
+
+            let page = ak.wikipage(id)?;
+            let file = dir.join(format!("{page}.wiki"));
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            log::info!("writing {} to {file:?}", ak.name());
+            fs::write(&file, template.render(ak)?)
+                .with_context(|| format!("failed to write {file:?}"))?;
+            pages.push((page, ak.name().to_string()));
+        }
+    }
+
+    if index {
+        let index = pages
+            .iter()
+            .map(|(page, name)| format!("* [[{page}|{}]]\n", escape(name)))
+            .collect::<String>();
+        fs::write(dir.join("index.wiki"), index)?;
+    }
+
+    Ok(())
+}
+
+fn print_diff(page: &str, live: Option<String>, rendered: &str, color: bool) {
+    match unified_diff(page, live.as_deref().unwrap_or_default(), rendered, color) {
+        Some(diff) => print!("{diff}"),
+        None => log::debug!("{page} is up to date"),
+    }
+}
+
+pub(crate) async fn diff(config: &Config, source: SourceArgs) -> Result<()> {
+    let events = events(config, &source).await?;
+    let state = State::load(config.state_file.as_deref())?;
+    let mut komapedia = KoMapedia::connect(&config.komapedia).await?;
+    let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+
+    for (id, event) in events.iter().sorted_by_key(|&(id, _)| id) {
+        for (ak_id, ak) in event.aks() {
+            if !ak.is_koma() {
+                continue;
+            }
+
+            let previous = state
+                .get(*id, *ak_id)
+                .and_then(|state| state.page.as_deref());
+            for page in komapedia
+                .obsolete_pages_for_ak(*id, event, ak, previous)
+                .await?
+            {
+                print_diff(&page, komapedia.page_text(&page).await?, "", color);
+            }
+
+            let page = ak.wikipage(*id)?;
+            print_diff(
+                &page,
+                komapedia.page_text(&page).await?,
+                &komapedia.wikitext(ak)?,
+                color,
+            );
+        }
+
+        for page in komapedia.obsolete_pages(*id, event, &state).await? {
+            print_diff(&page, komapedia.page_text(&page).await?, "", color);
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn prune(config: &Config, source: SourceArgs) -> Result<()> {
+    let _lock = config.lock_file.as_deref().map(lock).transpose()?;
+    let events = events(config, &source).await?;
+    let mut state = State::load(config.state_file.as_deref())?;
+    let mut komapedia = KoMapedia::login(&config.komapedia).await?;
+
+    for (id, ref event) in events {
+        komapedia
+            .delete_old_pages(id, event, &mut state, &mut Report::default())
+            .await?;
+        state.save()?;
+    }
+
+    Ok(())
+}
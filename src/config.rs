@@ -0,0 +1,313 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{
+    env,
+    fs::read_to_string,
+    net::{Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::komapedia::Markup;
+
+/// Configuration of aksync, read from the TOML file given on the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// File locked while synchronising or pruning, to prevent overlapping runs.
+    pub(crate) lock_file: Option<PathBuf>,
+    /// File to remember the synchronised AKs in, enabling incremental runs.
+    pub(crate) state_file: Option<PathBuf>,
+    /// File to write metrics to after each run, e.g., for the textfile
+    /// collector of the Prometheus node exporter.
+    pub(crate) metrics_file: Option<PathBuf>,
+    pub(crate) aktool: AKToolConfig,
+    pub(crate) komapedia: KoMapediaConfig,
+    pub(crate) serve: ServeConfig,
+    pub(crate) notify: NotifyConfig,
+}
+
+impl Config {
+    pub(crate) fn load(path: Option<&Path>) -> Result<Self> {
+        match path {
+            None => Ok(Self::default()),
+            Some(path) => toml::from_str(
+                &read_to_string(path)
+                    .with_context(|| format!("failed to read config file {path:?}"))?,
+            )
+            .with_context(|| format!("invalid config file {path:?}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct AKToolConfig {
+    pub(crate) authentication: AKToolAuthentication,
+    /// Timeout for a single request, in seconds.
+    pub(crate) timeout: u64,
+    /// Directory for caching responses, enabling conditional requests.
+    pub(crate) cache_dir: Option<PathBuf>,
+    pub(crate) retry: RetryConfig,
+}
+
+impl Default for AKToolConfig {
+    fn default() -> Self {
+        Self {
+            authentication: AKToolAuthentication::default(),
+            timeout: 30,
+            cache_dir: None,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct KoMapediaConfig {
+    pub(crate) authentication: Authentication,
+    /// Value of the `maxlag` parameter sent with every request, in seconds.
+    pub(crate) maxlag: u64,
+    /// Page to add a summary of each sync run to, e.g., `KoMa_92/AKsync-Log`.
+    /// Runs that found all pages up to date are not logged.
+    pub(crate) log_page: Option<String>,
+    /// Template for the wikitext of AK pages, see `templates/ak.wiki`
+    /// for the default.
+    pub(crate) template: Option<PathBuf>,
+    /// Markup to keep in descriptions, via the `wiki_markup` filter:
+    /// `none`, `safe`, or `markdown` to convert Markdown to wikitext.
+    pub(crate) markup: Markup,
+    /// File mapping aktool owners to KoMapedia user names, see
+    /// `Users` for the format.
+    pub(crate) users: Option<PathBuf>,
+    /// Subpage of each event page to list the AKs of each owner on,
+    /// e.g., `AK-Leitungen`.
+    pub(crate) owners_page: Option<String>,
+    pub(crate) retry: RetryConfig,
+}
+
+impl Default for KoMapediaConfig {
+    fn default() -> Self {
+        Self {
+            authentication: Authentication::default(),
+            maxlag: 5,
+            log_page: None,
+            template: None,
+            markup: Markup::default(),
+            users: None,
+            owners_page: None,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// Settings for `aksync serve`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct ServeConfig {
+    /// Address to listen on for sync requests.
+    pub(crate) listen: SocketAddr,
+    /// Seconds without further requests to wait before starting a sync.
+    pub(crate) debounce: u64,
+    /// Seconds after which to sync even if nobody asked us to.
+    pub(crate) interval: Option<u64>,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from((Ipv6Addr::LOCALHOST, 8017)),
+            debounce: 10,
+            interval: None,
+        }
+    }
+}
+
+/// When and how to notify someone about sync runs needing attention.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct NotifyConfig {
+    /// Notify about runs deleting more pages than this, in addition to
+    /// runs with errors or skipped pages.
+    pub(crate) deletion_threshold: usize,
+    pub(crate) email: Option<EmailConfig>,
+    pub(crate) webhook: Option<WebhookConfig>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            deletion_threshold: 5,
+            email: None,
+            webhook: None,
+        }
+    }
+}
+
+/// Notification by email.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct EmailConfig {
+    /// The SMTP server, e.g., `smtps://mail.example.org` or `smtp://localhost:25`.
+    pub(crate) server: String,
+    /// User to log in as, with the password from `AKSYNC_SMTP_PASSWORD`.
+    #[serde(default)]
+    pub(crate) username: Option<String>,
+    pub(crate) from: String,
+    pub(crate) to: Vec<String>,
+}
+
+/// Notification by a POST request with a JSON body containing the
+/// report as `text`, as understood by Matrix and Mattermost bridges.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct WebhookConfig {
+    pub(crate) url: String,
+}
+
+/// How aksync authenticates against aktool, using the secret from
+/// `AKSYNC_AKTOOL_TOKEN`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum AKToolAuthentication {
+    /// Anonymous access, only public data is available.
+    #[default]
+    None,
+    /// An API token, sent in the `Authorization` header.
+    Token,
+    /// The id of a logged-in session, sent as a cookie.
+    Session,
+}
+
+/// How aksync authenticates against KoMapedia.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Authentication {
+    /// Log in as the bot user, with the password from `AKSYNC_BOT_PASSWORD`.
+    #[default]
+    BotPassword,
+    /// Use the access token of an OAuth 2.0 owner-only consumer from
+    /// `AKSYNC_OAUTH_TOKEN`.
+    #[serde(rename = "oauth")]
+    OAuth,
+}
+
+/// How often and how long to wait when retrying failed requests.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct RetryConfig {
+    /// Number of retries before giving up.
+    pub(crate) max_retries: u32,
+    /// Delay before the first retry, in seconds; doubled for each further retry.
+    pub(crate) initial_delay: u64,
+    /// Upper bound for the delay between retries, in seconds. We give up
+    /// if the server asks us to wait longer.
+    pub(crate) max_delay: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay: 1,
+            max_delay: 60,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Delay before retry number `retry` (starting at zero), unless
+    /// the server asked for a specific delay.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(2u64.saturating_pow(retry))
+            .min(self.max_delay);
+
+        Duration::from_secs(delay)
+    }
+}
+
+/// Read a secret from the file named in `{variable}_FILE`, falling back
+/// to the value of `variable` itself.
+pub(crate) fn secret_from_env(variable: &str) -> Result<String> {
+    match env::var(format!("{variable}_FILE")) {
+        Ok(path) => Ok(read_to_string(&path)
+            .with_context(|| format!("failed to read secret from {path:?}"))?
+            .trim()
+            .to_string()),
+        Err(_) => env::var(variable).with_context(|| format!("{variable} is not set")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::Path, time::Duration};
+
+    use test_log::test;
+
+    use super::{AKToolAuthentication, Authentication, Config, RetryConfig};
+
+    #[test]
+    fn parse_config() {
+        let config = toml::from_str::<Config>(
+            r#"
+[aktool]
+authentication = "token"
+cache-dir = "/var/cache/aksync"
+
+[komapedia]
+authentication = "oauth"
+maxlag = 3
+
+[komapedia.retry]
+max-retries = 2
+
+[serve]
+listen = "127.0.0.1:8080"
+
+[notify.email]
+server = "smtp://localhost:25"
+from = "aksync@example.org"
+to = ["orga@example.org"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.aktool.authentication, AKToolAuthentication::Token);
+        assert_eq!(config.aktool.timeout, 30);
+        assert_eq!(
+            config.aktool.cache_dir.as_deref(),
+            Some(Path::new("/var/cache/aksync"))
+        );
+        assert_eq!(config.komapedia.authentication, Authentication::OAuth);
+        assert_eq!(config.komapedia.maxlag, 3);
+        assert_eq!(config.komapedia.retry.max_retries, 2);
+        assert_eq!(config.komapedia.retry.max_delay, 60);
+        assert_eq!(config.serve.listen.port(), 8080);
+        assert_eq!(config.serve.debounce, 10);
+        assert_eq!(config.notify.deletion_threshold, 5);
+        assert_eq!(config.notify.email.unwrap().to, vec!["orga@example.org"]);
+        assert!(config.notify.webhook.is_none());
+    }
+
+    #[test]
+    fn backoff() {
+        let retry = RetryConfig {
+            max_retries: 10,
+            initial_delay: 2,
+            max_delay: 30,
+        };
+
+        assert_eq!(retry.backoff(0), Duration::from_secs(2));
+        assert_eq!(retry.backoff(3), Duration::from_secs(16));
+        assert_eq!(retry.backoff(4), Duration::from_secs(30));
+        assert_eq!(retry.backoff(100), Duration::from_secs(30));
+    }
+}
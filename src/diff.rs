@@ -0,0 +1,89 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use similar::TextDiff;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A unified diff between the `live` text of `page` on KoMapedia and the
+/// `rendered` text, or `None` if they agree up to trailing whitespace,
+/// which MediaWiki strips when saving.
+pub(crate) fn unified_diff(page: &str, live: &str, rendered: &str, color: bool) -> Option<String> {
+    let live = normalise(live);
+    let rendered = normalise(rendered);
+
+    if live == rendered {
+        return None;
+    }
+
+    let diff = TextDiff::from_lines(&live, &rendered)
+        .unified_diff()
+        .header(&format!("{page} (KoMapedia)"), &format!("{page} (aksync)"))
+        .to_string();
+
+    if !color {
+        return Some(diff);
+    }
+
+    Some(
+        diff.lines()
+            .map(|line| {
+                let style = if line.starts_with("---") || line.starts_with("+++") {
+                    BOLD
+                } else if line.starts_with("@@") {
+                    CYAN
+                } else if line.starts_with('-') {
+                    RED
+                } else if line.starts_with('+') {
+                    GREEN
+                } else {
+                    return format!("{line}\n");
+                };
+
+                format!("{style}{line}{RESET}\n")
+            })
+            .collect(),
+    )
+}
+
+fn normalise(text: &str) -> String {
+    let text = text.trim_end();
+
+    if text.is_empty() {
+        String::new()
+    } else {
+        format!("{text}\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_log::test;
+
+    use super::unified_diff;
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(unified_diff("A", "x\ny", "x\ny\n\n", false), None);
+    }
+
+    #[test]
+    fn changed() {
+        assert_eq!(
+            unified_diff("A", "x\ny\n", "x\nz\n", false).unwrap(),
+            "--- A (KoMapedia)\n+++ A (aksync)\n@@ -1,2 +1,2 @@\n x\n-y\n+z\n"
+        );
+    }
+
+    #[test]
+    fn deleted() {
+        let diff = unified_diff("A", "x\n", "", true).unwrap();
+        assert!(diff.contains("\x1b[31m-x\x1b[0m\n"));
+    }
+}
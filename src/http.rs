@@ -0,0 +1,140 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Result, bail};
+use reqwest::{
+    StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
+
+use crate::config::RetryConfig;
+
+/// Whether a request that failed with `status` is worth retrying.
+pub(crate) fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// The delay requested by a `Retry-After` header, given either in
+/// seconds or as an HTTP date.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .ok(),
+    }
+}
+
+/// Wait before sending a failed request again, or give up if we have
+/// already retried often enough. A `delay` requested by the server
+/// takes precedence over the configured backoff; if it exceeds the
+/// configured maximum, we give up instead of retrying early.
+pub(crate) async fn backoff(
+    config: &RetryConfig,
+    retry: u32,
+    reason: &str,
+    delay: Option<Duration>,
+) -> Result<()> {
+    if retry >= config.max_retries {
+        bail!("{reason}, giving up after {retry} retries");
+    }
+
+    let delay = match delay {
+        Some(delay) if delay > Duration::from_secs(config.max_delay) => {
+            bail!("{reason}, giving up as the server asked to wait {delay:?}")
+        }
+        Some(delay) => delay,
+        None => config.backoff(retry),
+    };
+    log::warn!("{reason}, retrying in {delay:?}");
+    tokio::time::sleep(delay).await;
+
+    Ok(())
+}
+
+/// Serve `app` as a stand-in for a remote service, returning its base IRI.
+#[cfg(test)]
+pub(crate) async fn serve(app: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    format!("http://{address}")
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use test_log::test;
+
+    use super::{backoff, retry_after};
+    use crate::config::RetryConfig;
+
+    #[test]
+    fn parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[test(tokio::test)]
+    async fn requested_delay() {
+        let config = RetryConfig {
+            max_delay: 1,
+            ..Default::default()
+        };
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            backoff(&config, 0, "got HTTP status 503", Some(Duration::ZERO)),
+        )
+        .await
+        .expect("the delay requested by the server was not used")
+        .unwrap();
+        assert!(
+            tokio::time::timeout(
+                Duration::from_millis(500),
+                backoff(
+                    &config,
+                    0,
+                    "got HTTP status 503",
+                    Some(Duration::from_secs(1))
+                ),
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            backoff(
+                &config,
+                0,
+                "got HTTP status 503",
+                Some(Duration::from_secs(3600))
+            )
+            .await
+            .is_err()
+        );
+        assert!(
+            backoff(&config, config.max_retries, "failed", Some(Duration::ZERO))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -3,77 +3,123 @@
 //
 // SPDX-License-Identifier: EUPL-1.2
 
-use std::{collections::HashSet, env, fs::read_to_string};
+mod error;
+mod escape;
+mod link;
+mod markdown;
+mod title;
+
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Instant, SystemTime},
+};
 
 use anyhow::{Result, anyhow, bail};
+use itertools::Itertools;
 use mediawiki::Api;
-use serde_json::Value;
+use serde_json::{Map, Value};
+
+use error::{ApiError, Policy};
+pub(crate) use escape::{Markup, escape, escape_markup, escape_url};
+use link::{KOMAPEDIA_USER_NAMESPACES, parse_pagelink};
+pub(crate) use link::{user_name, user_page};
+pub(crate) use title::subpage_title;
 
 use crate::{
     AKSYNC_USER_AGENT,
-    model::{AK, AKId, Event, EventId, aktool::EVENT_KOMA92},
+    config::{Authentication, KoMapediaConfig, secret_from_env},
+    http,
+    metrics::{AKOutcome, METRICS, Service},
+    model::{AK, AKId, Event, EventId, Selection, aktool::EVENT_KOMA92},
+    report::Report,
+    state::{State, content_hash},
+    template::Template,
 };
 
-const KOMAPEDIA_DOMAINS: &[&str] = &[
-    "https://de.komapedia.org",
-    "https://komapedia.org",
-    "https://www.komapedia.org",
-];
-
 const KOMAPEDIA_ENDPOINT: &str = "https://de.komapedia.org/api.php";
 const KOMAPEDIA_BOT_USERNAME: &str = "AKsync";
 
-const KOMAPEDIA_PAGE_PREFIXES: &[&str] = &["/wiki/", "/index.php?title="];
-
 pub(crate) const KOMAPEDIA_AK_PREFIX: &str = "AK ";
 pub(crate) const KOMAPEDIA_EVENTS: &[(EventId, &str)] = &[(EVENT_KOMA92, "KoMa_92")];
-pub(crate) const AKSYNC_AK_TEMPLATE: &str = "KoMa Externer AK aus aktool";
+/// Template marking pages generated by aksync.
 pub(crate) const AKSYNC_GENERATED_TEMPLATE: &str = "Seite automatisch erzeugt von aksync";
 pub(crate) const AKSYNC_SUMMARY: &str = "AK-Liste aus aktool importiert";
 pub(crate) const AKSYNC_DELETE_SUMMARY: &str = "AK wurde in aktool gelöscht";
 
-fn is_pagelink(target: &str) -> Option<String> {
-    for &domain in KOMAPEDIA_DOMAINS {
-        if let Some(relative) = target.strip_prefix(domain) {
-            for &prefix in KOMAPEDIA_PAGE_PREFIXES {
-                if let Some(link) = relative.strip_prefix(prefix) {
-                    let index = link.find('&').unwrap_or(link.len());
-
-                    return Some(link[..index].to_string());
-                }
-            }
-        }
-    }
-
-    None
-}
-
 pub(crate) fn is_subpage(target: &str, event: EventId, ak: &AK) -> Option<String> {
     let prefix = format!("{}/", ak.wikipage(event).ok()?).replace(' ', "_");
 
-    is_pagelink(target).and_then(|t| t.strip_prefix(&prefix).map(|t| t.to_string()))
+    parse_pagelink(target).and_then(|link| link.title.strip_prefix(&prefix).map(str::to_string))
 }
 
 pub(crate) fn format_link(label: String, target: &Option<String>) -> String {
     match target {
         Some(link) => {
-            if let Some(link) = is_pagelink(link) {
+            if let Some(link) = parse_pagelink(link) {
                 format!("[[{link}|{label}]]")
             } else {
-                format!("[{link} {label}]")
+                format!("[{} {label}]", escape_url(link))
             }
         }
         None => label,
     }
 }
 
-pub(crate) fn escape(text: &str) -> String {
-    let result = text.to_string();
+/// A link to the user page of `user`.
+pub(crate) fn format_user_link(label: String, user: &str) -> String {
+    format!("[[{}:{user}|{label}]]", KOMAPEDIA_USER_NAMESPACES[0])
+}
+
+/// Wikitext listing the AKs of each owner of `event`.
+fn owners_page_text(id: EventId, event: &Event) -> Result<String> {
+    let mut text = format!("{{{{{AKSYNC_GENERATED_TEMPLATE}}}}}\n");
+
+    for owner in event.owners() {
+        let aks = event
+            .aks()
+            .filter(|(_, ak)| ak.is_koma() && ak.has_owner(owner))
+            .collect::<Vec<_>>();
+        if aks.is_empty() {
+            continue;
+        }
+
+        text.push_str(&format!("\n== AKs von {owner} ==\n"));
+        for (_, ak) in aks {
+            text.push_str(&format!(
+                "* [[{}|{}]]\n",
+                ak.wikipage(id)?,
+                escape(ak.name())
+            ));
+        }
+    }
+
+    Ok(text)
+}
+
+/// Wikitext summarising a sync run, for the log page.
+fn log_entry(report: &Report) -> String {
+    let mut entry = match &report.error {
+        Some(error) => format!("Synchronisation fehlgeschlagen: {}\n\n", escape(error)),
+        None => "Synchronisation erfolgreich.\n\n".to_string(),
+    };
 
-    let result = result.replace("{{", "{&ZeroWidthSpace;{");
-    let result = result.replace("}}", "}&ZeroWidthSpace;}");
-    let result = result.replace('|', "{{!}}");
-    result.replace('=', "{{=}}")
+    entry.push_str(&format!("* Aktualisiert: {}\n", report.synced.len()));
+    entry.push_str(&format!("* Unverändert: {}\n", report.unchanged));
+    entry.push_str(&format!("* Gelöscht: {}\n", report.deleted.len()));
+    for page in &report.deleted {
+        entry.push_str(&format!("** [[{page}]]\n"));
+    }
+    for (title, pages) in [
+        ("Übersprungen", &report.skipped),
+        ("Fehlgeschlagen", &report.failed),
+    ] {
+        entry.push_str(&format!("* {title}: {}\n", pages.len()));
+        for (page, reason) in pages {
+            entry.push_str(&format!("** [[{page}]]: {}\n", escape(reason)));
+        }
+    }
+
+    entry
 }
 
 pub(crate) fn wikipage(event: EventId) -> Result<String> {
@@ -83,186 +129,501 @@ pub(crate) fn wikipage(event: EventId) -> Result<String> {
         .ok_or(anyhow!("unknown event {event:?}"))
 }
 
-async fn delete_old_pages(api: &mut Api, id: EventId, event: &Event) -> Result<()> {
-    log::info!("Checking for AKs deleted from aktool");
-    let parameters = api.params_into(&[
-        ("action", "ask"),
-        ("query", &AK::semantic_query_all_aks(id)),
-        ("formatversion", "2"),
-    ]);
-
-    log::debug!("API request:\n{parameters:#?}");
-
-    let result = api.get_query_api_json(&parameters).await?;
-    log::debug!("{result:#?}");
-    let aks = event.aks().map(|(id, _)| *id).collect::<HashSet<_>>();
-
-    if let Value::Object(map) = result {
-        if let Some(Value::Object(map)) = map.get("query") {
-            if let Some(Value::Object(map)) = map.get("results") {
-                for (page, values) in map.iter() {
-                    if let Some(Value::Object(map)) = values.get("printouts") {
-                        if let Some(Value::Array(list)) = map.get("Aktool id") {
-                            let ak_ids = list
-                                .iter()
-                                .filter_map(|value| {
-                                    if let Value::Number(id) = value {
-                                        id.as_u64().map(AKId::new)
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .collect::<HashSet<_>>();
-
-                            if ak_ids.is_disjoint(&aks) {
-                                log::debug!("obsolete AK {page:?}");
-                                let token = api.get_edit_token().await?;
-                                let parameters = api.params_into(&[
-                                    ("action", "delete"),
-                                    ("title", page),
-                                    ("reason", AKSYNC_DELETE_SUMMARY),
-                                    ("bot", "true"),
-                                    ("token", &token),
-                                ]);
-                                log::debug!("API request:\n{parameters:#?}");
-                                log::info!("Deleting obsolete page {page}");
-                                let result = api.post_query_api_json(&parameters).await?;
-
-                                if let Value::Object(map) = result {
-                                    if let Some(Value::Object(err)) = map.get("error") {
-                                        let code = err
-                                            .get("code")
-                                            .unwrap_or(&Value::String("<unknown>".to_string()))
-                                            .to_string();
-
-                                        // page may have already been deleted, don't bail in that case
-                                        if code != "missingtitle" {
-                                            bail!(
-                                                "got error {code}: {}",
-                                                err.get("info")
-                                                    .unwrap_or(&Value::String(Default::default()))
-                                            )
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+/// Result of a request that did not fail.
+enum Outcome {
+    Done(Value),
+    Skipped(ApiError),
+}
+
+/// Result of saving the page of an AK.
+pub(crate) enum Edit {
+    /// The page was saved, as the given revision if it changed.
+    Saved(Option<u64>),
+    /// The edit was skipped because of the given error.
+    Skipped(ApiError),
+}
+
+/// A logged-in session on KoMapedia, shared across all events of a run.
+pub(crate) struct KoMapedia {
+    api: Api,
+    config: KoMapediaConfig,
+    token: Option<String>,
+    template: Template,
+}
+
+impl KoMapedia {
+    /// Connect to KoMapedia without logging in, which suffices for reading.
+    pub(crate) async fn connect(config: &KoMapediaConfig) -> Result<Self> {
+        let mut api = Api::new(KOMAPEDIA_ENDPOINT).await?;
+        api.set_user_agent(AKSYNC_USER_AGENT);
+
+        Ok(Self {
+            api,
+            config: config.clone(),
+            token: None,
+            template: Template::load(config)?,
+        })
+    }
+
+    pub(crate) async fn login(config: &KoMapediaConfig) -> Result<Self> {
+        let mut komapedia = Self::connect(config).await?;
+
+        match config.authentication {
+            Authentication::BotPassword => {
+                komapedia
+                    .api
+                    .login(
+                        KOMAPEDIA_BOT_USERNAME,
+                        &secret_from_env("AKSYNC_BOT_PASSWORD")?,
+                    )
+                    .await?
             }
+            Authentication::OAuth => komapedia
+                .api
+                .set_oauth2(&secret_from_env("AKSYNC_OAUTH_TOKEN")?),
         }
+
+        komapedia.check_login().await?;
+
+        Ok(komapedia)
     }
 
-    Ok(())
-}
+    /// Make sure that we are not editing anonymously.
+    async fn check_login(&self) -> Result<()> {
+        let parameters = self
+            .api
+            .params_into(&[("action", "query"), ("meta", "userinfo")]);
+        let result = self.send(&parameters, "GET").await??;
+        let user = result
+            .pointer("/query/userinfo")
+            .ok_or_else(|| anyhow!("no user info in response"))?;
+
+        if user.get("anon").is_some() {
+            bail!("not logged in to KoMapedia");
+        }
 
-async fn delete_old_pages_for_ak(api: &mut Api, event: EventId, ak: &AK) -> Result<()> {
-    let parameters = api.params_into(&[
-        ("action", "ask"),
-        ("query", &ak.semantic_query(event)),
-        ("formatversion", "2"),
-    ]);
-
-    log::debug!("API request:\n{parameters:#?}");
-
-    let result = api.get_query_api_json(&parameters).await?;
-    log::debug!("{result:#?}");
-    if let Value::Object(map) = result {
-        if let Some(Value::Object(map)) = map.get("query") {
-            if let Some(Value::Object(map)) = map.get("results") {
-                for page in map.keys() {
-                    let page = page.replace(' ', "_");
-                    if ak.wikipage(event)? != page {
-                        log::debug!("{page:?}, {:?}", ak.wikipage(event)?);
-                        let token = api.get_edit_token().await?;
-                        let parameters = api.params_into(&[
-                            ("action", "delete"),
-                            ("title", &page),
-                            ("reason", AKSYNC_DELETE_SUMMARY),
-                            ("bot", "true"),
-                            ("token", &token),
-                        ]);
-                        log::debug!("API request:\n{parameters:#?}");
-                        log::info!("Deleting obsolete page {page}");
-                        let result = api.post_query_api_json(&parameters).await?;
-
-                        if let Value::Object(map) = result {
-                            if let Some(Value::Object(err)) = map.get("error") {
-                                bail!(
-                                    "got error {}: {}",
-                                    err.get("code")
-                                        .unwrap_or(&Value::String("<unknown>".to_string()))
-                                        .to_string(),
-                                    err.get("info")
-                                        .unwrap_or(&Value::String(Default::default()))
-                                )
-                            }
-                        }
+        log::info!(
+            "logged in to KoMapedia as {}",
+            user.get("name").and_then(Value::as_str).unwrap_or_default()
+        );
+        Ok(())
+    }
+
+    /// Send a request to the action API, retrying with backoff on
+    /// transient HTTP errors and on errors with [`Policy::Retry`].
+    async fn send(
+        &self,
+        params: &HashMap<String, String>,
+        method: &str,
+    ) -> Result<Result<Value, ApiError>> {
+        let mut parameters = params.clone();
+        parameters.insert("format".to_string(), "json".to_string());
+        parameters.insert("maxlag".to_string(), self.config.maxlag.to_string());
+
+        log::debug!("API request:\n{parameters:#?}");
+
+        let mut retry = 0;
+        loop {
+            let start = Instant::now();
+            let record = |success| METRICS.request(Service::KoMapedia, start.elapsed(), success);
+            let response = self
+                .api
+                .get_api_request_builder(&parameters, method)?
+                .send()
+                .await
+                .inspect_err(|_| record(false))?;
+            let status = response.status();
+            let mut delay = http::retry_after(response.headers());
+
+            let reason = if http::is_transient(status) {
+                record(false);
+                format!("got HTTP status {status}")
+            } else {
+                let result = response
+                    .error_for_status()
+                    .inspect_err(|_| record(false))?
+                    .json::<Value>()
+                    .await
+                    .inspect_err(|_| record(false))?;
+                log::debug!("{result:#?}");
+
+                let result = ApiError::check(result);
+                record(result.is_ok());
+                match result {
+                    Err(error) if error.policy() == Policy::Retry => {
+                        delay = delay.or(error.retry_after());
+                        error.to_string()
                     }
+                    result => return Ok(result),
+                }
+            };
+
+            http::backoff(&self.config.retry, retry, &reason, delay).await?;
+            retry += 1;
+        }
+    }
+
+    /// The CSRF token of the session, fetched on first use.
+    async fn token(&mut self) -> Result<String> {
+        if let Some(token) = &self.token {
+            return Ok(token.clone());
+        }
+
+        let parameters =
+            self.api
+                .params_into(&[("action", "query"), ("meta", "tokens"), ("type", "csrf")]);
+        let token = self
+            .send(&parameters, "GET")
+            .await??
+            .pointer("/query/tokens/csrftoken")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("no CSRF token in response"))?;
+
+        self.token = Some(token.clone());
+        Ok(token)
+    }
+
+    /// Send a request to the action API and apply the [`Policy`] of any
+    /// [`ApiError`] in the response. If `write` is set, the request is
+    /// sent as a POST including a CSRF token.
+    async fn request(&mut self, params: &[(&str, &str)], write: bool) -> Result<Outcome> {
+        let method = if write { "POST" } else { "GET" };
+        let mut parameters = self.api.params_into(params);
+        let mut refreshed = 0;
+
+        loop {
+            if write {
+                parameters.insert("token".to_string(), self.token().await?);
+            }
+
+            let error = match self.send(&parameters, method).await? {
+                Ok(result) => return Ok(Outcome::Done(result)),
+                Err(error) => error,
+            };
+
+            match error.policy() {
+                Policy::RefreshToken if write && refreshed < self.config.retry.max_retries => {
+                    log::warn!("{error}, refreshing token");
+                    self.token = None;
+                    refreshed += 1;
+                }
+                Policy::Skip => {
+                    log::warn!("{error}, skipping");
+                    return Ok(Outcome::Skipped(error));
                 }
+                _ => return Err(error.into()),
             }
         }
     }
 
-    Ok(())
-}
+    /// Run a semantic query, returning the result pages with their printouts.
+    async fn ask(&mut self, query: &str) -> Result<Map<String, Value>> {
+        let params = [("action", "ask"), ("query", query), ("formatversion", "2")];
+
+        match self.request(&params, false).await? {
+            Outcome::Done(result) => Ok(result
+                .pointer("/query/results")
+                .and_then(Value::as_object)
+                .cloned()
+                .unwrap_or_default()),
+            Outcome::Skipped(error) => bail!(error),
+        }
+    }
 
-pub(crate) async fn update_ak(api: &mut Api, event: EventId, ak: &AK) -> Result<()> {
-    delete_old_pages_for_ak(api, event, ak).await?;
-
-    let token = api.get_edit_token().await?;
-    let parameters = api.params_into(&[
-        ("action", "edit"),
-        ("title", &ak.wikipage(event)?),
-        ("text", &ak.wikitext()),
-        ("summary", AKSYNC_SUMMARY),
-        ("bot", "true"),
-        ("watchlist", "unwatch"),
-        ("token", &token),
-    ]);
-
-    log::debug!("API request:\n{parameters:#?}");
-
-    let result = api.post_query_api_json(&parameters).await?;
-
-    if let Value::Object(map) = result {
-        if let Some(Value::Object(err)) = map.get("error") {
-            bail!(
-                "got error {}: {}",
-                err.get("code")
-                    .unwrap_or(&Value::String("<unknown>".to_string()))
-                    .to_string(),
-                err.get("info")
-                    .unwrap_or(&Value::String(Default::default()))
+    /// The current wikitext of `page`, if the page exists.
+    pub(crate) async fn page_text(&mut self, page: &str) -> Result<Option<String>> {
+        let params = [
+            ("action", "query"),
+            ("prop", "revisions"),
+            ("rvprop", "content"),
+            ("rvslots", "main"),
+            ("titles", page),
+            ("formatversion", "2"),
+        ];
+
+        match self.request(&params, false).await? {
+            Outcome::Done(result) => Ok(result
+                .pointer("/query/pages/0/revisions/0/slots/main/content")
+                .and_then(Value::as_str)
+                .map(str::to_string)),
+            Outcome::Skipped(error) => bail!(error),
+        }
+    }
+
+    async fn delete_page(&mut self, page: &str, report: &mut Report) -> Result<()> {
+        log::info!(page, action = "delete"; "Deleting obsolete page {page}");
+        let outcome = self
+            .request(
+                &[
+                    ("action", "delete"),
+                    ("title", page),
+                    ("reason", AKSYNC_DELETE_SUMMARY),
+                    ("bot", "true"),
+                ],
+                true,
             )
+            .await?;
+
+        match outcome {
+            Outcome::Done(_) => {
+                log::info!(page, action = "delete", outcome = "deleted"; "deleted {page}");
+                METRICS.ak(AKOutcome::Deleted);
+                report.deleted.push(page.to_string());
+            }
+            Outcome::Skipped(error) => report.skipped.push((page.to_string(), error.to_string())),
         }
+
+        Ok(())
     }
 
-    Ok(())
-}
+    /// Pages of AKs of the event that no longer exist in aktool,
+    /// according to both the semantic data and our state.
+    pub(crate) async fn obsolete_pages(
+        &mut self,
+        id: EventId,
+        event: &Event,
+        state: &State,
+    ) -> Result<Vec<String>> {
+        let results = self.ask(&AK::semantic_query_all_aks(id)).await?;
+        let aks = event.aks().map(|(id, _)| *id).collect::<HashSet<_>>();
+        let current = event.pages(id)?;
+        let mut pages = state.removed(id, &aks);
+
+        for (page, values) in results.iter() {
+            if let Some(Value::Array(list)) = values.pointer("/printouts/Aktool id") {
+                let ak_ids = list
+                    .iter()
+                    .filter_map(|value| value.as_u64().map(AKId::new))
+                    .collect::<HashSet<_>>();
+
+                if ak_ids.is_disjoint(&aks) {
+                    log::debug!("obsolete AK {page:?}");
+                    pages.push(page.replace(' ', "_"));
+                }
+            }
+        }
+
+        // another AK may have taken over the page of a removed one
+        Ok(pages
+            .into_iter()
+            .filter(|page| !current.contains(page))
+            .unique()
+            .collect())
+    }
+
+    /// Pages of an AK other than its current page, e.g., after it was
+    /// renamed, including the `previous` page we saved it as. Pages that
+    /// are now the current page of any AK of the event are kept.
+    pub(crate) async fn obsolete_pages_for_ak(
+        &mut self,
+        id: EventId,
+        event: &Event,
+        ak: &AK,
+        previous: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let results = self.ask(&ak.semantic_query(id)).await?;
+        let page = ak.wikipage(id)?;
+        let current = event.pages(id)?;
+
+        Ok(results
+            .keys()
+            .map(|result| result.replace(' ', "_"))
+            .chain(previous.map(str::to_string))
+            .filter(|result| !current.contains(result))
+            .inspect(|result| log::debug!("{result:?}, {page:?}"))
+            .unique()
+            .collect())
+    }
+
+    pub(crate) async fn delete_old_pages(
+        &mut self,
+        id: EventId,
+        event: &Event,
+        state: &mut State,
+        report: &mut Report,
+    ) -> Result<()> {
+        log::info!("Checking for AKs deleted from aktool");
+
+        for page in self.obsolete_pages(id, event, state).await? {
+            self.delete_page(&page, report).await?;
+        }
+
+        state.retain(id, &event.aks().map(|(id, _)| *id).collect());
+
+        Ok(())
+    }
 
-pub(crate) async fn update_event(id: EventId, event: &Event) -> Result<()> {
-    let mut api = Api::new(KOMAPEDIA_ENDPOINT).await?;
-    api.set_user_agent(AKSYNC_USER_AGENT);
-    api.login(KOMAPEDIA_BOT_USERNAME, &bot_password_from_env()?)
-        .await?;
+    /// Save the page of an AK, deleting any `previous` page. The edit is
+    /// rejected as a conflict if the page was changed after `base_revision`.
+    pub(crate) async fn update_ak(
+        &mut self,
+        id: EventId,
+        event: &Event,
+        ak: &AK,
+        previous: Option<&str>,
+        base_revision: Option<u64>,
+        report: &mut Report,
+    ) -> Result<Edit> {
+        for page in self.obsolete_pages_for_ak(id, event, ak, previous).await? {
+            self.delete_page(&page, report).await?;
+        }
 
-    for (_, ak) in event.aks() {
-        if ak.is_koma() {
-            log::info!("processing {} ({})", ak.name(), ak.wikipage(id)?);
-            update_ak(&mut api, id, ak).await?;
+        let page = ak.wikipage(id)?;
+        let text = self.wikitext(ak)?;
+        let base_revision = base_revision.map(|revision| revision.to_string());
+        let mut params = vec![
+            ("action", "edit"),
+            ("title", page.as_str()),
+            ("text", &text),
+            ("summary", AKSYNC_SUMMARY),
+            ("bot", "true"),
+            ("watchlist", "unwatch"),
+        ];
+        if let Some(base_revision) = &base_revision {
+            params.push(("baserevid", base_revision));
         }
+
+        Ok(match self.request(&params, true).await? {
+            Outcome::Done(result) => {
+                Edit::Saved(result.pointer("/edit/newrevid").and_then(Value::as_u64))
+            }
+            Outcome::Skipped(error) => Edit::Skipped(error),
+        })
     }
 
-    delete_old_pages(&mut api, id, event).await?;
+    /// The wikitext for the page of `ak`.
+    pub(crate) fn wikitext(&self, ak: &AK) -> Result<String> {
+        self.template.render(ak)
+    }
 
-    Ok(())
-}
+    /// Add a section summarising the run to `page`.
+    pub(crate) async fn append_log(&mut self, page: &str, report: &Report) -> Result<()> {
+        log::info!(page, action = "log"; "adding run summary to {page}");
+        let title = httpdate::fmt_http_date(SystemTime::now());
+        let params = [
+            ("action", "edit"),
+            ("title", page),
+            ("section", "new"),
+            ("sectiontitle", &title),
+            ("text", &log_entry(report)),
+            ("bot", "true"),
+            ("watchlist", "nochange"),
+        ];
+
+        match self.request(&params, true).await? {
+            Outcome::Done(_) => Ok(()),
+            Outcome::Skipped(error) => bail!(error),
+        }
+    }
+
+    /// Update the subpage of the event page listing the AKs of each owner.
+    pub(crate) async fn update_owners_page(
+        &mut self,
+        id: EventId,
+        event: &Event,
+        subpage: &str,
+    ) -> Result<()> {
+        let page = subpage_title(&wikipage(id)?, subpage, "");
+        log::info!(event = id, page = page.as_str(), action = "owners"; "updating AKs by owner on {page}");
+        let params = [
+            ("action", "edit"),
+            ("title", page.as_str()),
+            ("text", &owners_page_text(id, event)?),
+            ("summary", AKSYNC_SUMMARY),
+            ("bot", "true"),
+            ("watchlist", "nochange"),
+        ];
+
+        match self.request(&params, true).await? {
+            Outcome::Done(_) => Ok(()),
+            Outcome::Skipped(error) => bail!(error),
+        }
+    }
+
+    /// Save the pages of the selected AKs of `event`. An AK that fails
+    /// does not keep the others from being updated.
+    pub(crate) async fn update_event(
+        &mut self,
+        id: EventId,
+        event: &Event,
+        selection: &Selection,
+        state: &mut State,
+        report: &mut Report,
+    ) -> Result<()> {
+        let mut failed = 0;
+        for (&ak_id, ak) in event.aks() {
+            if !ak.is_koma() || !selection.contains(ak) {
+                continue;
+            }
+
+            let page = ak.wikipage(id)?;
+            let hash = content_hash(&page, &self.wikitext(ak)?);
+            if state.is_current(id, ak_id, &hash) {
+                log::debug!(
+                    event = id, ak = ak_id, page = page.as_str(), action = "edit",
+                    outcome = "unchanged";
+                    "{} ({page}) is unchanged", ak.name()
+                );
+                METRICS.ak(AKOutcome::Unchanged);
+                report.unchanged += 1;
+                continue;
+            }
 
-fn bot_password_from_env() -> Result<String> {
-    match env::var("AKSYNC_BOT_PASSWORD_FILE") {
-        Ok(path) => Ok(read_to_string(path)?.trim().to_string()),
-        Err(_) => Ok(env::var("AKSYNC_BOT_PASSWORD")?),
+            let previous = state.get(id, ak_id).and_then(|state| state.page.clone());
+            let base_revision = state.base_revision(id, ak_id, &page);
+
+            log::info!(
+                event = id, ak = ak_id, page = page.as_str(), action = "edit";
+                "processing {} ({page})", ak.name()
+            );
+            match self
+                .update_ak(id, event, ak, previous.as_deref(), base_revision, report)
+                .await
+            {
+                Err(err) => {
+                    log::error!(
+                        event = id, ak = ak_id, page = page.as_str(), action = "edit",
+                        outcome = "failed";
+                        "failed to update {page}: {err:#}"
+                    );
+                    METRICS.ak(AKOutcome::Failed);
+                    report.failed.push((page, format!("{err:#}")));
+                    failed += 1;
+                }
+                Ok(Edit::Saved(revision)) => {
+                    log::info!(
+                        event = id, ak = ak_id, page = page.as_str(), action = "edit",
+                        outcome = "synced", revision:serde;
+                        "updated {page}"
+                    );
+                    METRICS.ak(AKOutcome::Synced);
+                    report.synced.push(page.clone());
+                    state.record(id, ak_id, page, revision, hash);
+                }
+                Ok(Edit::Skipped(error)) => {
+                    log::warn!(
+                        event = id, ak = ak_id, page = page.as_str(), action = "edit",
+                        outcome = "skipped", error = error.code();
+                        "not updating {page}: {error}"
+                    );
+                    METRICS.ak(AKOutcome::Skipped);
+                    if let ApiError::EditConflict { .. } = error {
+                        state.conflict(id, ak_id);
+                    }
+                    if state.skip(id, ak_id) {
+                        report.newly_skipped += 1;
+                    }
+                    report.skipped.push((page, error.to_string()));
+                }
+            }
+        }
+
+        if failed > 0 {
+            bail!("failed to update {failed} AKs of event {id}");
+        }
+
+        Ok(())
     }
 }
 
@@ -273,11 +634,13 @@ mod test {
     use test_log::test;
 
     use crate::{
-        komapedia::is_subpage,
+        komapedia::{is_subpage, log_entry, owners_page_text},
         model::{
-            AK,
+            AK, Event,
             aktool::{self, EVENT_KOMA92},
         },
+        report::Report,
+        users::Users,
     };
 
     #[test]
@@ -297,4 +660,75 @@ mod test {
             Some("Ergebnis".to_string())
         );
     }
+
+    #[test]
+    fn log() {
+        let report = Report {
+            synced: vec!["KoMa_92/AK_Testwurst".to_string()],
+            unchanged: 2,
+            deleted: vec!["KoMa_92/AK_Alt".to_string()],
+            failed: vec![(
+                "KoMa_92/AK_Wurst".to_string(),
+                "got error abusefilter-disallowed: <b>Nope</b>".to_string(),
+            )],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            log_entry(&report),
+            "Synchronisation erfolgreich.
+
+* Aktualisiert: 1
+* Unverändert: 2
+* Gelöscht: 1
+** [[KoMa_92/AK_Alt]]
+* Übersprungen: 0
+* Fehlgeschlagen: 1
+** [[KoMa_92/AK_Wurst]]: got error abusefilter-disallowed: &lt;b&gt;Nope&lt;/b&gt;
+"
+        );
+    }
+
+    #[test]
+    fn owners() {
+        let mut event = Event::new(
+            [serde_json::from_str::<aktool::Category>(
+                r##"{"id":64,"name":"Inhalt/Arbeit","color":"#487eb0","description":"","present_by_default":false,"event":16}"##,
+            )
+            .unwrap()],
+            [
+                r#"{"id":1312,"name":"mmarx","slug":"mmarx","institution":"TU Dresden","link":"https://de.komapedia.org/wiki/Benutzer:Mmarx","event":16}"#,
+                r#"{"id":1313,"name":"Anna|Lena","slug":"al","institution":"","link":"","event":16}"#,
+                r#"{"id":1314,"name":"Gast","slug":"gast","institution":"","link":"","event":16}"#,
+            ]
+            .map(|owner| serde_json::from_str::<aktool::Owner>(owner).unwrap()),
+            &Users::default(),
+        );
+        for (id, name, owners) in [
+            (1305, "IT-Infrastruktur", "[1312,1313]"),
+            (1306, "Testwurst", "[1312]"),
+        ] {
+            event
+                .add_ak(
+                    serde_json::from_str::<aktool::AK>(&format!(
+                        r#"{{"id":{id},"name":"{name}","short_name":"{name}","description":"","link":"","protocol_link":"","reso":false,"present":null,"notes":"","interest":-1,"interest_counter":0,"include_in_export":true,"category":64,"track":null,"event":16,"owners":{owners},"types":[2],"requirements":[],"conflicts":[],"prerequisites":[]}}"#
+                    ))
+                    .unwrap(),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            owners_page_text(EVENT_KOMA92, &event).unwrap(),
+            "{{Seite automatisch erzeugt von aksync}}
+
+== AKs von Anna&#124;Lena ==
+* [[KoMa_92/AK_IT-Infrastruktur|AK IT-Infrastruktur]]
+
+== AKs von [[Benutzer:Mmarx|mmarx]] (TU Dresden) ==
+* [[KoMa_92/AK_IT-Infrastruktur|AK IT-Infrastruktur]]
+* [[KoMa_92/AK_Testwurst|AK Testwurst]]
+"
+        );
+    }
 }
@@ -0,0 +1,193 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use serde_json::Value;
+
+/// An error reported by the MediaWiki action API in the `error` object of a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ApiError {
+    /// The replicas lag behind more than the requested `maxlag`.
+    MaxLag { lag: Option<u64>, info: String },
+    /// We are making too many requests.
+    RateLimited { info: String },
+    /// The CSRF token is invalid, e.g., because the session expired.
+    BadToken { info: String },
+    /// The page is protected and we lack the rights to change it.
+    ProtectedPage { info: String },
+    /// The page does not exist (anymore).
+    MissingTitle { info: String },
+    /// Someone else edited the page since our last edit.
+    EditConflict { info: String },
+    /// An abuse filter rejected the edit.
+    AbuseFilter { code: String, info: String },
+    /// Any other error.
+    Other { code: String, info: String },
+}
+
+/// What to do when a request fails with a given [`ApiError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Policy {
+    /// Wait for a while, then send the same request again.
+    Retry,
+    /// Obtain a new CSRF token, then send the request again.
+    RefreshToken,
+    /// Give up on this request, but carry on with the run.
+    Skip,
+    /// Abort the run.
+    Fail,
+}
+
+impl ApiError {
+    /// Check a response of the action API, returning the response if it
+    /// does not contain an error.
+    pub(crate) fn check(response: Value) -> Result<Value, Self> {
+        match response.get("error") {
+            Some(error) => Err(Self::from_error(error)),
+            None => Ok(response),
+        }
+    }
+
+    fn from_error(error: &Value) -> Self {
+        let code = error
+            .get("code")
+            .and_then(Value::as_str)
+            .unwrap_or("<unknown>")
+            .to_string();
+        let info = error
+            .get("info")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        match code.as_str() {
+            "maxlag" => Self::MaxLag {
+                lag: error.get("lag").and_then(Value::as_u64),
+                info,
+            },
+            "ratelimited" => Self::RateLimited { info },
+            "badtoken" => Self::BadToken { info },
+            "protectedpage" | "cascadeprotected" => Self::ProtectedPage { info },
+            "missingtitle" => Self::MissingTitle { info },
+            "editconflict" => Self::EditConflict { info },
+            _ if code.starts_with("abusefilter") => Self::AbuseFilter { code, info },
+            _ => Self::Other { code, info },
+        }
+    }
+
+    pub(crate) fn code(&self) -> &str {
+        match self {
+            Self::MaxLag { .. } => "maxlag",
+            Self::RateLimited { .. } => "ratelimited",
+            Self::BadToken { .. } => "badtoken",
+            Self::ProtectedPage { .. } => "protectedpage",
+            Self::MissingTitle { .. } => "missingtitle",
+            Self::EditConflict { .. } => "editconflict",
+            Self::AbuseFilter { code, .. } | Self::Other { code, .. } => code,
+        }
+    }
+
+    pub(crate) fn info(&self) -> &str {
+        match self {
+            Self::MaxLag { info, .. }
+            | Self::RateLimited { info }
+            | Self::BadToken { info }
+            | Self::ProtectedPage { info }
+            | Self::MissingTitle { info }
+            | Self::EditConflict { info }
+            | Self::AbuseFilter { info, .. }
+            | Self::Other { info, .. } => info,
+        }
+    }
+
+    /// How long to wait before retrying, if the error tells us.
+    pub(crate) fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::MaxLag { lag: Some(lag), .. } => Some(Duration::from_secs(*lag)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn policy(&self) -> Policy {
+        match self {
+            Self::MaxLag { .. } | Self::RateLimited { .. } => Policy::Retry,
+            Self::BadToken { .. } => Policy::RefreshToken,
+            // page may have already been deleted, may have been edited
+            // by hand, or may be protected or filtered by a wiki admin;
+            // none of these should stop the run
+            Self::MissingTitle { .. }
+            | Self::EditConflict { .. }
+            | Self::ProtectedPage { .. }
+            | Self::AbuseFilter { .. } => Policy::Skip,
+            Self::Other { .. } => Policy::Fail,
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "got error {}: {}", self.code(), self.info())
+    }
+}
+
+impl Error for ApiError {}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+    use test_log::test;
+
+    use super::{ApiError, Policy};
+
+    #[test]
+    fn success() {
+        let response = json!({"delete": {"title": "KoMa_92/AK_Test"}});
+        assert_eq!(ApiError::check(response.clone()), Ok(response));
+    }
+
+    #[test]
+    fn typed_errors() {
+        let response = json!({"error": {"code": "maxlag", "info": "Waiting for a database server: 7 seconds lagged.", "lag": 7}});
+        let error = ApiError::check(response).unwrap_err();
+        assert!(matches!(error, ApiError::MaxLag { lag: Some(7), .. }));
+        assert_eq!(error.policy(), Policy::Retry);
+
+        let response = json!({"error": {"code": "missingtitle", "info": "The page you specified doesn't exist."}});
+        let error = ApiError::check(response).unwrap_err();
+        assert_eq!(
+            error,
+            ApiError::MissingTitle {
+                info: "The page you specified doesn't exist.".to_string()
+            }
+        );
+        assert_eq!(error.policy(), Policy::Skip);
+
+        let response = json!({"error": {"code": "abusefilter-disallowed", "info": "nope"}});
+        let error = ApiError::check(response).unwrap_err();
+        assert_eq!(error.code(), "abusefilter-disallowed");
+        assert_eq!(error.policy(), Policy::Skip);
+    }
+
+    #[test]
+    fn policies() {
+        let error =
+            |code: &str| ApiError::check(json!({"error": {"code": code, "info": ""}})).unwrap_err();
+
+        assert_eq!(error("ratelimited").policy(), Policy::Retry);
+        assert_eq!(error("badtoken").policy(), Policy::RefreshToken);
+        assert_eq!(error("protectedpage").policy(), Policy::Skip);
+        assert_eq!(error("editconflict").policy(), Policy::Skip);
+        assert_eq!(error("permissiondenied").policy(), Policy::Fail);
+        assert_eq!(
+            error("permissiondenied").to_string(),
+            "got error permissiondenied: "
+        );
+    }
+}
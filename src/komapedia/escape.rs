@@ -0,0 +1,251 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use serde::Deserialize;
+
+use super::markdown;
+
+/// How much wiki markup to keep when escaping text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Markup {
+    /// Escape everything, so the text renders literally.
+    #[default]
+    None,
+    /// Keep bold and italic text and lists, escape everything else.
+    Safe,
+    /// Convert Markdown to wikitext, escaping everything else.
+    Markdown,
+}
+
+/// Characters that always start or delimit markup: tags, comments,
+/// links, templates, parameters, tables, and named template arguments.
+const SPECIAL: &[char] = &['<', '>', '[', ']', '{', '}', '|', '='];
+
+/// Characters that are markup only when repeated: emphasis,
+/// signatures, and behaviour switches like `__NOTOC__`.
+const REPEATED: &[char] = &['\'', '~', '_'];
+
+/// Characters that are markup at the start of a line: lists,
+/// definitions, preformatted text, horizontal rules, and table headers.
+const LINE_START: &[char] = &['*', '#', ':', ';', ' ', '\t', '-', '!'];
+
+/// Characters that end the target of an external link, or are not
+/// allowed in URLs anyway.
+const URL_END: &[char] = &['[', ']', '<', '>', '"', '{', '}', '|'];
+
+/// Markers of list items, kept for [`Markup::Safe`].
+const LIST: &[char] = &['*', '#'];
+
+/// Characters that change list items when following their markers.
+const AFTER_LIST: &[char] = &[':', ';'];
+
+fn reference(result: &mut String, c: char) {
+    match c {
+        '&' => result.push_str("&amp;"),
+        '<' => result.push_str("&lt;"),
+        '>' => result.push_str("&gt;"),
+        c => result.push_str(&format!("&#{};", c as u32)),
+    }
+}
+
+/// Escape `text` so that it renders literally, also as the value of a
+/// template parameter.
+pub(crate) fn escape(text: &str) -> String {
+    escape_markup(text, Markup::None)
+}
+
+/// Escape `url` for use as the target of an external link. Characters
+/// ending the link are percent-encoded, other markup is replaced by
+/// character references, which MediaWiki resolves in link targets.
+pub(crate) fn escape_url(url: &str) -> String {
+    let chars = url.chars().collect::<Vec<_>>();
+    let mut result = String::with_capacity(url.len());
+
+    for (index, &c) in chars.iter().enumerate() {
+        let repeated = || (index > 0 && chars[index - 1] == c) || chars.get(index + 1) == Some(&c);
+        if c.is_whitespace() || c.is_control() || URL_END.contains(&c) {
+            for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                result.push_str(&format!("%{byte:02X}"));
+            }
+        } else if c == '=' || (REPEATED.contains(&c) && repeated()) {
+            reference(&mut result, c);
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Escape `text` for use in the middle of a line.
+pub(super) fn escape_inline(text: &str) -> String {
+    escape_lines(text, Markup::None, true)
+}
+
+/// Escape `text`, keeping only the given `markup`. Special characters
+/// are replaced by character references, which MediaWiki resolves only
+/// after parsing the markup.
+pub(crate) fn escape_markup(text: &str, markup: Markup) -> String {
+    match markup {
+        Markup::Markdown => markdown::to_wikitext(text),
+        markup => escape_lines(text, markup, false),
+    }
+}
+
+fn escape_lines(text: &str, markup: Markup, inline: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for (number, line) in text.split('\n').enumerate() {
+        if number > 0 {
+            result.push('\n');
+        }
+
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut start = 0;
+        if markup == Markup::Safe {
+            while start < chars.len() && LIST.contains(&chars[start]) {
+                result.push(chars[start]);
+                start += 1;
+            }
+        }
+        let line_start = match (start, number) {
+            (0, 0) if inline => &[],
+            (0, _) => LINE_START,
+            _ => AFTER_LIST,
+        };
+
+        for (index, &c) in chars.iter().enumerate().skip(start) {
+            let repeated =
+                || (index > 0 && chars[index - 1] == c) || chars.get(index + 1) == Some(&c);
+            let is_markup = c == '&'
+                || SPECIAL.contains(&c)
+                || (index == start && line_start.contains(&c))
+                || (REPEATED.contains(&c) && repeated() && !(markup == Markup::Safe && c == '\''));
+
+            if is_markup {
+                reference(&mut result, c);
+            } else {
+                result.push(c);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+    use regex::{Captures, Regex};
+    use test_log::test;
+
+    use super::{Markup, escape, escape_markup};
+    use crate::komapedia::markdown::to_wikitext;
+
+    /// Resolve the character references produced by escaping.
+    fn unescape(text: &str) -> String {
+        Regex::new("&(amp|lt|gt|#[0-9]+);")
+            .unwrap()
+            .replace_all(text, |captures: &Captures| match &captures[1] {
+                "amp" => "&".to_string(),
+                "lt" => "<".to_string(),
+                "gt" => ">".to_string(),
+                code => char::from_u32(code[1..].parse().unwrap())
+                    .unwrap()
+                    .to_string(),
+            })
+            .into_owned()
+    }
+
+    /// Whether `text` contains markup other than what `markup` allows.
+    fn is_inert(text: &str, markup: Markup) -> bool {
+        let repeated = match markup {
+            Markup::None => &["''", "~~", "__"][..],
+            Markup::Safe => &["~~", "__"][..],
+            Markup::Markdown => &["''", "~~", "__"][..],
+        };
+
+        !text.contains(['<', '>', '[', ']', '{', '}', '|', '='])
+            && !repeated.iter().any(|pattern| text.contains(pattern))
+            && text.split('\n').all(|line| match markup {
+                Markup::None => !line.starts_with(['*', '#', ':', ';', ' ', '\t', '-', '!']),
+                Markup::Safe => match line.trim_start_matches(['*', '#']) {
+                    rest if rest.len() < line.len() => !rest.starts_with([':', ';']),
+                    _ => !line.starts_with([':', ';', ' ', '\t', '-', '!']),
+                },
+                Markup::Markdown => true,
+            })
+    }
+
+    /// Text full of wiki markup.
+    fn wikitext() -> impl Strategy<Value = String> {
+        proptest::string::string_regex(
+            "([a-zäöü0-9 \n]|[\\[\\]{}|=<>&'~_*#:;!\\-\t]|__NOTOC__|~~~~|<nowiki>|<ref>|&amp;|&#61;)*",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn examples() {
+        assert_eq!(escape("AK IT-Infrastruktur"), "AK IT-Infrastruktur");
+        assert_eq!(escape("Anna|Lena"), "Anna&#124;Lena");
+        assert_eq!(escape("{{Vorlage}}"), "&#123;&#123;Vorlage&#125;&#125;");
+        assert_eq!(
+            escape("[[Kategorie:Test]]"),
+            "&#91;&#91;Kategorie:Test&#93;&#93;"
+        );
+        assert_eq!(escape("<nowiki>"), "&lt;nowiki&gt;");
+        assert_eq!(escape("Gruß ~~~~"), "Gruß &#126;&#126;&#126;&#126;");
+        assert_eq!(escape("__NOTOC__ a_b"), "&#95;&#95;NOTOC&#95;&#95; a_b");
+        assert_eq!(escape("* eins\n: zwei"), "&#42; eins\n&#58; zwei");
+        assert_eq!(escape("''kursiv'' it's"), "&#39;&#39;kursiv&#39;&#39; it's");
+        assert_eq!(
+            escape_markup("* ''kursiv''\n*# '''fett'''\n:x", Markup::Safe),
+            "* ''kursiv''\n*# '''fett'''\n&#58;x"
+        );
+        assert_eq!(
+            escape_markup("** {{a}}", Markup::Safe),
+            "** &#123;&#123;a&#125;&#125;"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(text in any::<String>()) {
+            prop_assert_eq!(unescape(&escape(&text)), text);
+        }
+
+        #[test]
+        fn renders_literally(text in wikitext()) {
+            let escaped = escape(&text);
+            prop_assert_eq!(unescape(&escaped), text.as_str());
+            prop_assert!(is_inert(&escaped, Markup::None), "{:?}", escaped);
+        }
+
+        #[test]
+        fn markdown_links(
+            label in "[a-z]+",
+            url in "https://example\\.org/([a-z0-9 \\[\\]{}|=&'~_*#:;!\\-]|''|~~~~|__NOTOC__)*",
+        ) {
+            let markdown = format!("[{label}](<{url}>)");
+            let wikitext = to_wikitext(&markdown);
+            let target = wikitext
+                .strip_prefix('[')
+                .and_then(|link| link.strip_suffix(&format!(" {label}]")));
+            prop_assert!(target.is_some(), "{:?}", wikitext);
+            let target = target.unwrap();
+            prop_assert!(!target.contains(char::is_whitespace), "{:?}", wikitext);
+            prop_assert!(is_inert(target, Markup::Markdown), "{:?}", wikitext);
+        }
+
+        #[test]
+        fn keeps_safe_markup(text in wikitext()) {
+            let escaped = escape_markup(&text, Markup::Safe);
+            prop_assert_eq!(unescape(&escaped), text.as_str());
+            prop_assert!(is_inert(&escaped, Markup::Safe), "{:?}", escaped);
+        }
+    }
+}
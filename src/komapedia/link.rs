@@ -0,0 +1,238 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::fmt::{self, Display};
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+/// Hosts serving KoMapedia, including the mobile version.
+const KOMAPEDIA_HOSTS: &[&str] = &[
+    "de.komapedia.org",
+    "komapedia.org",
+    "www.komapedia.org",
+    "de.m.komapedia.org",
+    "m.komapedia.org",
+];
+
+/// Path prefix of pretty page URLs.
+const KOMAPEDIA_ARTICLE_PATH: &str = "/wiki/";
+
+/// Path of the script taking the title as a query parameter.
+const KOMAPEDIA_SCRIPT: &str = "/index.php";
+
+/// Interwiki prefixes that point back to KoMapedia itself.
+const KOMAPEDIA_INTERWIKI: &[&str] = &["de"];
+
+/// Names of the user namespace, the first one being canonical.
+pub(crate) const KOMAPEDIA_USER_NAMESPACES: &[&str] = &["Benutzer", "Benutzerin", "User"];
+
+/// Namespaces whose pages are not linked but used when linking to
+/// them: links to categories categorise the page, links to files
+/// embed them.
+const KOMAPEDIA_EMBEDDED_NAMESPACES: &[&str] =
+    &["Kategorie", "Category", "Datei", "File", "Bild", "Image"];
+
+/// Characters that can never occur in a page title.
+const ILLEGAL: &[char] = &['<', '>', '[', ']', '{', '}', '|', '#'];
+
+/// A link to a page on KoMapedia.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PageLink {
+    /// The title, with underscores instead of spaces.
+    pub(crate) title: String,
+    /// The section, if any.
+    pub(crate) fragment: Option<String>,
+}
+
+impl Display for PageLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((namespace, _)) = self.title.split_once(':')
+            && KOMAPEDIA_EMBEDDED_NAMESPACES
+                .iter()
+                .any(|embedded| embedded.eq_ignore_ascii_case(namespace))
+        {
+            // link to the page instead
+            write!(f, ":")?;
+        }
+
+        match &self.fragment {
+            Some(fragment) => write!(f, "{}#{fragment}", self.title),
+            None => write!(f, "{}", self.title),
+        }
+    }
+}
+
+fn decode(text: &str) -> Option<String> {
+    percent_decode_str(text)
+        .decode_utf8()
+        .ok()
+        .map(|text| text.into_owned())
+}
+
+/// Normalise a title the way MediaWiki does: underscores for spaces,
+/// no surrounding whitespace, no interwiki prefixes pointing back to
+/// KoMapedia, and a capitalised first letter.
+fn normalise(title: &str) -> Option<String> {
+    let mut title = title.replace(' ', "_").trim_matches('_').to_string();
+    while let Some((prefix, rest)) = title.trim_start_matches(':').split_once(':')
+        && KOMAPEDIA_INTERWIKI
+            .iter()
+            .any(|interwiki| interwiki.eq_ignore_ascii_case(prefix.trim_end_matches('_')))
+    {
+        title = rest.trim_start_matches('_').to_string();
+    }
+    let title = title.trim_start_matches(':');
+
+    if title.is_empty()
+        || title.contains(ILLEGAL)
+        || title.contains(char::is_control)
+        || title.contains("~~~")
+    {
+        return None;
+    }
+
+    let mut chars = title.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+}
+
+/// The user whose user page, or a subpage of it, is `title`.
+fn user_of(title: &str) -> Option<String> {
+    let (namespace, page) = title.split_once(':')?;
+    KOMAPEDIA_USER_NAMESPACES
+        .iter()
+        .any(|user| user.eq_ignore_ascii_case(namespace))
+        .then(|| normalise(page.split('/').next()?))?
+}
+
+/// Normalise a user name, which may include the user namespace.
+pub(crate) fn user_name(name: &str) -> Option<String> {
+    let title = normalise(name)?;
+    user_of(&title).or(Some(title))
+}
+
+/// The user whose user page `target` links to.
+pub(crate) fn user_page(target: &str) -> Option<String> {
+    parse_pagelink(target).and_then(|link| user_of(&link.title))
+}
+
+/// Parse a URL pointing to a page on KoMapedia.
+pub(crate) fn parse_pagelink(target: &str) -> Option<PageLink> {
+    let url = Url::parse(target.trim()).ok()?;
+    if !matches!(url.scheme(), "http" | "https")
+        || !KOMAPEDIA_HOSTS.contains(&url.host_str()?.to_ascii_lowercase().as_str())
+    {
+        return None;
+    }
+
+    let title = match url.path().strip_prefix(KOMAPEDIA_ARTICLE_PATH) {
+        Some(title) => decode(title)?,
+        None if url.path() == KOMAPEDIA_SCRIPT => url
+            .query_pairs()
+            .find_map(|(key, value)| (key == "title").then(|| value.into_owned()))?,
+        None => return None,
+    };
+    let fragment = url
+        .fragment()
+        .and_then(decode)
+        .map(|fragment| fragment.replace(' ', "_"))
+        .filter(|fragment| !fragment.is_empty() && !fragment.contains(ILLEGAL));
+
+    Some(PageLink {
+        title: normalise(&title)?,
+        fragment,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use test_log::test;
+
+    use super::{PageLink, parse_pagelink, user_name, user_page};
+
+    fn title(target: &str) -> Option<String> {
+        parse_pagelink(target).map(|link| link.to_string())
+    }
+
+    #[test]
+    fn pagelinks() {
+        let page = Some("KoMa_92/AK_Über_uns".to_string());
+        for target in [
+            "https://de.komapedia.org/wiki/KoMa_92/AK_Über_uns",
+            "https://de.komapedia.org/wiki/KoMa_92/AK_%C3%9Cber_uns",
+            "http://komapedia.org/wiki/KoMa%2092/AK%20%C3%9Cber%20uns",
+            "https://de.m.komapedia.org/wiki/KoMa_92/AK_Über_uns",
+            "https://de.komapedia.org/index.php?title=KoMa_92/AK_%C3%9Cber_uns",
+            "https://www.komapedia.org/index.php?action=edit&title=KoMa+92/AK+Über+uns",
+            "https://de.komapedia.org/wiki/de:KoMa_92/AK_Über_uns",
+            "https://de.komapedia.org/wiki/:DE:_koMa_92/AK_Über_uns",
+        ] {
+            assert_eq!(title(target), page, "{target}");
+        }
+
+        assert_eq!(
+            parse_pagelink("https://de.komapedia.org/wiki/KoMa_92#Zeitplan%20Samstag"),
+            Some(PageLink {
+                title: "KoMa_92".to_string(),
+                fragment: Some("Zeitplan_Samstag".to_string()),
+            })
+        );
+        assert_eq!(
+            title("https://de.komapedia.org/wiki/Benutzer:Mmarx"),
+            Some("Benutzer:Mmarx".to_string())
+        );
+        for (target, link) in [
+            (
+                "https://de.komapedia.org/wiki/Kategorie:KoMa_92",
+                ":Kategorie:KoMa_92",
+            ),
+            (
+                "https://de.komapedia.org/wiki/category:KoMa_92",
+                ":Category:KoMa_92",
+            ),
+            (
+                "https://de.komapedia.org/wiki/Datei:Logo.png",
+                ":Datei:Logo.png",
+            ),
+            (
+                "https://de.komapedia.org/index.php?title=Bild:Logo.png",
+                ":Bild:Logo.png",
+            ),
+        ] {
+            assert_eq!(title(target), Some(link.to_string()), "{target}");
+        }
+
+        for target in [
+            "https://wiki.kif.rocks/wiki/KIF530:IT-Infrastruktur",
+            "https://de.komapedia.org/",
+            "https://de.komapedia.org/index.php?action=edit",
+            "https://de.komapedia.org/wiki/A%5D%5D_%7B%7BB%7D%7D",
+            "https://de.komapedia.org/wiki/Gru%C3%9F_~~~~",
+            "ftp://de.komapedia.org/wiki/KoMa_92",
+            "KoMa_92",
+        ] {
+            assert_eq!(title(target), None, "{target}");
+        }
+    }
+
+    #[test]
+    fn users() {
+        let user = Some("Anna_Lena".to_string());
+        assert_eq!(user_name("anna Lena"), user);
+        assert_eq!(user_name("Benutzerin:Anna_Lena"), user);
+        assert_eq!(
+            user_page("https://de.komapedia.org/wiki/Benutzer:anna_Lena/AKs"),
+            user
+        );
+        assert_eq!(
+            user_page("https://de.komapedia.org/index.php?title=User:Anna%20Lena"),
+            user
+        );
+        assert_eq!(user_page("https://de.komapedia.org/wiki/KoMa_92"), None);
+        assert_eq!(user_page("https://de.komapedia.org/wiki/Benutzer:"), None);
+    }
+}
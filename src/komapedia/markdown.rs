@@ -0,0 +1,218 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::mem;
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use super::{
+    escape::{escape, escape_inline},
+    format_link,
+};
+
+/// Builds wikitext from the events of the Markdown parser.
+#[derive(Debug, Default)]
+struct Writer {
+    output: String,
+    /// Markers of the enclosing lists, innermost last.
+    lists: Vec<char>,
+    /// Targets of the enclosing links, with the output before each link.
+    links: Vec<(String, String)>,
+    code_block: bool,
+    /// Whether the current list item has no content yet.
+    item: bool,
+}
+
+impl Writer {
+    fn push(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn newline(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        // apostrophes next to emphasis would change it
+        let text = if self.output.is_empty() || self.output.ends_with('\n') {
+            escape(text)
+        } else {
+            escape_inline(text)
+        };
+        self.push(&text.replace('\'', "&#39;"));
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            // paragraphs of loose lists stay in their item
+            Tag::Paragraph if !self.lists.is_empty() => {
+                if !mem::take(&mut self.item) {
+                    if self.output.ends_with('\n') {
+                        let markers = self.lists.iter().collect::<String>();
+                        self.push(&format!("{markers}: "));
+                    } else {
+                        self.push("<br />");
+                    }
+                }
+            }
+            Tag::Paragraph | Tag::Heading { .. } | Tag::BlockQuote(_) => {
+                self.newline();
+                match tag {
+                    Tag::Heading { .. } => self.push("'''"),
+                    Tag::BlockQuote(_) => self.push("<blockquote>\n"),
+                    _ => (),
+                }
+            }
+            Tag::CodeBlock(_) => {
+                self.newline();
+                self.push("<pre>");
+                self.code_block = true;
+            }
+            Tag::List(start) => {
+                self.newline();
+                self.lists.push(if start.is_some() { '#' } else { '*' });
+            }
+            Tag::Item => {
+                self.newline();
+                let markers = self.lists.iter().collect::<String>();
+                self.push(&format!("{markers} "));
+                self.item = true;
+            }
+            Tag::Emphasis => self.push("''"),
+            Tag::Strong => self.push("'''"),
+            Tag::Strikethrough => self.push("<s>"),
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                let before = mem::take(&mut self.output);
+                self.links.push((dest_url.into_string(), before));
+            }
+            _ => (),
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph if self.lists.is_empty() => self.push("\n\n"),
+            TagEnd::Heading(_) => self.push("'''\n\n"),
+            TagEnd::BlockQuote(_) => self.push("</blockquote>\n\n"),
+            TagEnd::CodeBlock => {
+                self.push("</pre>\n\n");
+                self.code_block = false;
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.push("\n");
+                }
+            }
+            TagEnd::Item => {
+                self.newline();
+                self.item = false;
+            }
+            TagEnd::Emphasis => self.push("''"),
+            TagEnd::Strong => self.push("'''"),
+            TagEnd::Strikethrough => self.push("</s>"),
+            TagEnd::Link | TagEnd::Image => {
+                if let Some((target, before)) = self.links.pop() {
+                    let label = mem::replace(&mut self.output, before);
+                    let link = format_link(label, &Some(target));
+                    self.push(&link);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Convert Markdown to wikitext. Raw HTML is escaped, and links to
+/// pages on KoMapedia become internal links.
+pub(crate) fn to_wikitext(markdown: &str) -> String {
+    let mut writer = Writer::default();
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Start(tag) => writer.start(tag),
+            Event::End(tag) => writer.end(tag),
+            Event::Text(text) if writer.code_block => writer.push(&escape(&text)),
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => writer.text(&text),
+            Event::Code(code) => writer.push(&format!("<code>{}</code>", escape(&code))),
+            Event::SoftBreak => writer.push(" "),
+            Event::HardBreak => writer.push("<br />"),
+            Event::Rule => {
+                writer.newline();
+                writer.push("----\n\n");
+            }
+            _ => (),
+        }
+    }
+
+    writer.output.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use test_log::test;
+
+    use super::to_wikitext;
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            to_wikitext(
+                "Wir *testen* **Würste**:
+
+- eins
+- [zwei](https://de.komapedia.org/wiki/KoMa_92)
+  1. drei
+
+Siehe [Doku](https://example.org/doku), `a|b`
+und <b>{{nicht}}</b>."
+            ),
+            "Wir ''testen'' '''Würste''':
+
+* eins
+* [[KoMa_92|zwei]]
+*# drei
+
+Siehe [https://example.org/doku Doku], <code>a&#124;b</code> und &lt;b&gt;&#123;&#123;nicht&#125;&#125;&lt;/b&gt;."
+        );
+    }
+
+    #[test]
+    fn external_links() {
+        assert_eq!(
+            to_wikitext("[x](https://example.org/a|Name=evil{{Vorlage}}~~~~''b'')"),
+            "[https://example.org/a%7CName&#61;evil%7B%7BVorlage%7D%7D&#126;&#126;&#126;&#126;&#39;&#39;b&#39;&#39; x]"
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(
+            to_wikitext(
+                "# Ziel\n\n```\n[[a]]\n```\n\n> zitiert\n\n---\n\n- eins\n\n- zwei\n\n  drei\n\n  - vier\n\n  fünf\n\nit's"
+            ),
+            "'''Ziel'''
+
+<pre>&#91;&#91;a&#93;&#93;
+</pre>
+
+<blockquote>
+zitiert
+
+</blockquote>
+
+----
+
+* eins
+* zwei<br />drei
+** vier
+*: fünf
+
+it&#39;s"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+/// Maximum length of a page title, in bytes.
+const MAX_TITLE_LENGTH: usize = 255;
+
+/// Replacements for characters that cannot occur in page titles.
+const REPLACEMENTS: &[(char, char)] = &[
+    ('[', '('),
+    (']', ')'),
+    ('{', '('),
+    ('}', ')'),
+    ('<', '-'),
+    ('>', '-'),
+    ('#', '-'),
+    ('|', '-'),
+];
+
+/// Whether `text` starts like something MediaWiki would decode in a
+/// title: a percent-encoded byte or a character reference.
+fn is_encoded(text: &str) -> bool {
+    if let Some(rest) = text.strip_prefix('%') {
+        return rest.bytes().take(2).filter(u8::is_ascii_hexdigit).count() == 2;
+    }
+    let Some((reference, _)) = text.strip_prefix('&').and_then(|rest| rest.split_once(';')) else {
+        return false;
+    };
+
+    let (digits, is_digit): (_, fn(&u8) -> bool) = match reference
+        .strip_prefix("#x")
+        .or(reference.strip_prefix("#X"))
+    {
+        Some(hex) => (hex, u8::is_ascii_hexdigit),
+        None => match reference.strip_prefix('#') {
+            Some(decimal) => (decimal, u8::is_ascii_digit),
+            None => (reference, u8::is_ascii_alphanumeric),
+        },
+    };
+    !digits.is_empty() && digits.bytes().all(|digit| is_digit(&digit))
+}
+
+/// Turn `name` into a valid title segment: illegal characters are
+/// replaced, whitespace becomes single underscores, tilde runs are
+/// shortened, and no part between slashes is empty or ends in a dot.
+fn sanitise(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for (index, c) in name.char_indices() {
+        let c = match REPLACEMENTS.iter().find(|&&(from, _)| from == c) {
+            Some(&(_, to)) => to,
+            None if c.is_whitespace() || c == '_' => '_',
+            None if c.is_control() => continue,
+            None if is_encoded(&name[index..]) => '-',
+            None if c == '~' && result.ends_with("~~") => continue,
+            None => c,
+        };
+        if !(c == '_' && result.ends_with('_')) {
+            result.push(c);
+        }
+    }
+
+    result
+        .split('/')
+        .map(|part| {
+            part.trim_matches('_')
+                .trim_end_matches('.')
+                .trim_end_matches('_')
+        })
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The title of the subpage `name` of `parent`, which must already be
+/// a valid title. The title is shortened to leave room for `suffix`.
+pub(crate) fn subpage_title(parent: &str, name: &str, suffix: &str) -> String {
+    let mut name = sanitise(name);
+    let length = MAX_TITLE_LENGTH.saturating_sub(parent.len() + 1 + suffix.len());
+    if name.len() > length {
+        name.truncate(name.floor_char_boundary(length));
+        name = sanitise(&name);
+    }
+
+    format!("{parent}/{name}{suffix}")
+}
+
+#[cfg(test)]
+mod test {
+    use test_log::test;
+
+    use super::{MAX_TITLE_LENGTH, subpage_title};
+
+    #[test]
+    fn titles() {
+        for (name, title) in [
+            ("AK IT-Infrastruktur", "KoMa_92/AK_IT-Infrastruktur"),
+            ("AK [Meta] {Reso}", "KoMa_92/AK_(Meta)_(Reso)"),
+            ("AK #1 <3 a|b", "KoMa_92/AK_-1_-3_a-b"),
+            ("ak  klein\t_ \n", "KoMa_92/ak_klein"),
+            ("AK Ende...", "KoMa_92/AK_Ende"),
+            ("AK a/./b/../c.", "KoMa_92/AK_a/b/c"),
+            ("AK ~~~~", "KoMa_92/AK_~~"),
+            (
+                "AK 100% %C3 &amp; &#61; & ;",
+                "KoMa_92/AK_100%_-C3_-amp;_--61;_&_;",
+            ),
+        ] {
+            assert_eq!(subpage_title("KoMa_92", name, ""), title, "{name:?}");
+        }
+
+        let long = "AK Über".repeat(100);
+        let title = subpage_title("KoMa_92", &long, "_(1305)");
+        assert!(title.len() <= MAX_TITLE_LENGTH);
+        assert!(title.starts_with("KoMa_92/AK_ÜberAK_Über"));
+        assert!(title.ends_with("_(1305)"));
+    }
+}
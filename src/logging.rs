@@ -0,0 +1,123 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{
+    io::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use env_logger::Env;
+use log::{
+    Record,
+    kv::{self, VisitSource},
+};
+use serde_json::{Map, Value};
+
+use crate::{cli::LogFormat, state::now};
+
+/// Id of the current sync run, included in JSON log messages.
+static RUN: AtomicU64 = AtomicU64::new(0);
+
+/// Start a new sync run, identified by its start time.
+pub(crate) fn start_run() {
+    RUN.store(now(), Ordering::Relaxed);
+}
+
+pub(crate) fn init(format: LogFormat) {
+    let mut builder = env_logger::Builder::from_env(Env::default().default_filter_or("info"));
+
+    if let LogFormat::Json = format {
+        builder.format(|buf, record| {
+            let mut line = json(record);
+            line.insert(
+                "timestamp".to_string(),
+                Value::String(buf.timestamp().to_string()),
+            );
+            if let run @ 1.. = RUN.load(Ordering::Relaxed) {
+                line.insert("run".to_string(), run.into());
+            }
+            writeln!(buf, "{}", Value::Object(line))
+        });
+    }
+
+    builder.init();
+}
+
+/// Collects the structured fields of a record.
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = serde_json::to_value(value).map_err(kv::Error::boxed)?;
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// The record as a JSON object, with its structured fields next to the
+/// level, target and message.
+fn json(record: &Record) -> Map<String, Value> {
+    let mut line = Map::new();
+    line.insert(
+        "level".to_string(),
+        Value::String(record.level().to_string()),
+    );
+    line.insert(
+        "target".to_string(),
+        Value::String(record.target().to_string()),
+    );
+    line.insert(
+        "message".to_string(),
+        Value::String(record.args().to_string()),
+    );
+
+    if let Err(err) = record.key_values().visit(&mut Fields(&mut line)) {
+        line.insert("error".to_string(), Value::String(err.to_string()));
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use log::{Level, kv::ToValue};
+    use serde_json::json;
+    use test_log::test;
+
+    use super::json;
+    use crate::model::{AKId, aktool::EVENT_KOMA92};
+
+    #[test]
+    fn structured_fields() {
+        let ak = AKId::new(1289);
+        let fields: &[(&str, log::kv::Value)] = &[
+            ("event", EVENT_KOMA92.to_value()),
+            ("ak", ak.to_value()),
+            ("page", "KoMa_92/AK_Testwurst".into()),
+            ("action", "edit".into()),
+        ];
+        let line = json(
+            &log::Record::builder()
+                .level(Level::Info)
+                .target("aksync::komapedia")
+                .args(format_args!("processing Testwursttesting"))
+                .key_values(&fields)
+                .build(),
+        );
+
+        assert_eq!(
+            serde_json::Value::Object(line),
+            json!({
+                "level": "INFO",
+                "target": "aksync::komapedia",
+                "message": "processing Testwursttesting",
+                "event": 16,
+                "ak": 1289,
+                "page": "KoMa_92/AK_Testwurst",
+                "action": "edit",
+            })
+        );
+    }
+}
@@ -5,16 +5,25 @@
 
 mod aktool;
 mod cli;
+mod commands;
+mod config;
+mod diff;
+mod http;
 mod komapedia;
+mod logging;
+mod metrics;
 mod model;
+mod notify;
+mod report;
+mod serve;
+mod state;
+mod template;
+mod users;
 
 use anyhow::Result;
 use clap::Parser;
-use cli::Cli;
-use env_logger::Env;
-
-use aktool::AKToolApi;
-use komapedia::{update_event, wikipage};
+use cli::{Cli, Command, SyncArgs};
+use config::Config;
 
 pub(crate) const AKSYNC_USER_AGENT: &str = concat!(
     env!("CARGO_PKG_NAME"),
@@ -26,22 +35,24 @@ pub(crate) const AKTOOL_ENDPOINT: &str = "https://ak.kif.rocks/KIFKoMa25/api";
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-
-    let _args = Cli::parse();
-
-    let aktool_api = AKToolApi::new(AKTOOL_ENDPOINT.to_string()).expect("should succeed");
-
-    log::info!("querying aktool");
-
-    let events = aktool_api.events().await?;
-
-    for (id, ref event) in events {
-        let wikipage = wikipage(id)?;
-        log::info!("processing event {id:?} ({wikipage})");
-        update_event(id, event).await?;
-        log::info!("updated AKs for KoMapedia page {wikipage}");
+    let args = Cli::parse();
+    logging::init(args.log_format);
+    let config = Config::load(args.config.as_deref())?;
+
+    match args
+        .command
+        .unwrap_or_else(|| Command::Sync(SyncArgs::default()))
+    {
+        Command::Sync(args) => commands::sync(&config, &args).await,
+        Command::Fetch { output } => commands::fetch(&config, output).await,
+        Command::Render {
+            ak,
+            output_dir,
+            index,
+            source,
+        } => commands::render(&config, ak, output_dir, index, source).await,
+        Command::Diff { source } => commands::diff(&config, source).await,
+        Command::Prune { source } => commands::prune(&config, source).await,
+        Command::Serve(args) => serve::serve(&config, &args).await,
     }
-
-    Ok(())
 }
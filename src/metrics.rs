@@ -0,0 +1,271 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{
+    fmt::Write as _,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use anyhow::Result;
+
+use crate::state::{now, write_atomically};
+
+/// Metrics of all runs of this process.
+pub(crate) static METRICS: Metrics = Metrics::new();
+
+/// A remote service queried by aksync.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Service {
+    AKTool,
+    KoMapedia,
+}
+
+impl Service {
+    fn label(&self) -> &'static str {
+        match self {
+            Service::AKTool => "aktool",
+            Service::KoMapedia => "komapedia",
+        }
+    }
+}
+
+/// What happened to an AK during a run.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum AKOutcome {
+    Synced,
+    Unchanged,
+    Skipped,
+    Failed,
+    Deleted,
+}
+
+impl AKOutcome {
+    const ALL: [Self; 5] = [
+        Self::Synced,
+        Self::Unchanged,
+        Self::Skipped,
+        Self::Failed,
+        Self::Deleted,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            AKOutcome::Synced => "synced",
+            AKOutcome::Unchanged => "unchanged",
+            AKOutcome::Skipped => "skipped",
+            AKOutcome::Failed => "failed",
+            AKOutcome::Deleted => "deleted",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Requests {
+    count: AtomicU64,
+    errors: AtomicU64,
+    /// Total duration of all requests, in microseconds.
+    duration: AtomicU64,
+}
+
+impl Requests {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            duration: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Counters for sync runs, exported in the Prometheus text format.
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    runs: AtomicU64,
+    failed_runs: AtomicU64,
+    last_run: AtomicU64,
+    last_success: AtomicU64,
+    aks: [AtomicU64; AKOutcome::ALL.len()],
+    requests: [Requests; 2],
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            runs: AtomicU64::new(0),
+            failed_runs: AtomicU64::new(0),
+            last_run: AtomicU64::new(0),
+            last_success: AtomicU64::new(0),
+            aks: [const { AtomicU64::new(0) }; AKOutcome::ALL.len()],
+            requests: [Requests::new(), Requests::new()],
+        }
+    }
+
+    /// Record a finished run.
+    pub(crate) fn run(&self, success: bool) {
+        let now = now();
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        self.last_run.store(now, Ordering::Relaxed);
+
+        if success {
+            self.last_success.store(now, Ordering::Relaxed);
+        } else {
+            self.failed_runs.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Seed the time of the last successful run, e.g., from a previous
+    /// process.
+    pub(crate) fn last_success(&self, timestamp: u64) {
+        self.last_success.fetch_max(timestamp, Ordering::Relaxed);
+    }
+
+    pub(crate) fn ak(&self, outcome: AKOutcome) {
+        self.aks[outcome as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a request to `service` that took `duration`.
+    pub(crate) fn request(&self, service: Service, duration: Duration, success: bool) {
+        let requests = &self.requests[service as usize];
+        requests.count.fetch_add(1, Ordering::Relaxed);
+        requests
+            .duration
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+
+        if !success {
+            requests.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub(crate) fn render(&self) -> String {
+        let mut output = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, values: &[(String, String)]| {
+            let _ = writeln!(output, "# HELP aksync_{name} {help}");
+            let _ = writeln!(output, "# TYPE aksync_{name} {kind}");
+            for (labels, value) in values {
+                let _ = writeln!(output, "aksync_{name}{labels} {value}");
+            }
+        };
+        let value = |counter: &AtomicU64| counter.load(Ordering::Relaxed).to_string();
+        let services = [Service::AKTool, Service::KoMapedia];
+        let per_service = |get: &dyn Fn(&Requests) -> String| {
+            services
+                .iter()
+                .map(|&service| {
+                    (
+                        format!("{{service=\"{}\"}}", service.label()),
+                        get(&self.requests[service as usize]),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        metric(
+            "runs_total",
+            "counter",
+            "Number of sync runs.",
+            &[(String::new(), value(&self.runs))],
+        );
+        metric(
+            "failed_runs_total",
+            "counter",
+            "Number of failed sync runs.",
+            &[(String::new(), value(&self.failed_runs))],
+        );
+        metric(
+            "last_run_timestamp_seconds",
+            "gauge",
+            "Time of the last sync run.",
+            &[(String::new(), value(&self.last_run))],
+        );
+        metric(
+            "last_success_timestamp_seconds",
+            "gauge",
+            "Time of the last successful sync run.",
+            &[(String::new(), value(&self.last_success))],
+        );
+        metric(
+            "aks_total",
+            "counter",
+            "Number of processed AKs, by outcome.",
+            &AKOutcome::ALL
+                .iter()
+                .map(|&outcome| {
+                    (
+                        format!("{{outcome=\"{}\"}}", outcome.label()),
+                        value(&self.aks[outcome as usize]),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
+        metric(
+            "requests_total",
+            "counter",
+            "Number of requests, by service.",
+            &per_service(&|requests| value(&requests.count)),
+        );
+        metric(
+            "request_errors_total",
+            "counter",
+            "Number of failed requests, by service.",
+            &per_service(&|requests| value(&requests.errors)),
+        );
+        metric(
+            "request_duration_seconds_total",
+            "counter",
+            "Total duration of requests, by service.",
+            &per_service(&|requests| {
+                (requests.duration.load(Ordering::Relaxed) as f64 / 1e6).to_string()
+            }),
+        );
+
+        output
+    }
+
+    /// Write the metrics to `path`, e.g., for the textfile collector of
+    /// the node exporter, which must never see a partially written file.
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        write_atomically(path, &self.render(), "metrics file")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use test_log::test;
+
+    use super::{AKOutcome, Metrics, Service};
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::new();
+        metrics.ak(AKOutcome::Synced);
+        metrics.ak(AKOutcome::Synced);
+        metrics.ak(AKOutcome::Deleted);
+        metrics.request(Service::KoMapedia, Duration::from_millis(1500), false);
+        metrics.last_success(1234);
+        metrics.run(false);
+
+        let output = metrics.render();
+        for line in [
+            "# TYPE aksync_runs_total counter",
+            "aksync_runs_total 1",
+            "aksync_failed_runs_total 1",
+            "aksync_last_success_timestamp_seconds 1234",
+            "aksync_aks_total{outcome=\"synced\"} 2",
+            "aksync_aks_total{outcome=\"deleted\"} 1",
+            "aksync_aks_total{outcome=\"failed\"} 0",
+            "aksync_requests_total{service=\"komapedia\"} 1",
+            "aksync_request_errors_total{service=\"komapedia\"} 1",
+            "aksync_request_errors_total{service=\"aktool\"} 0",
+            "aksync_request_duration_seconds_total{service=\"komapedia\"} 1.5",
+        ] {
+            assert!(output.lines().any(|l| l == line), "missing {line:?}");
+        }
+    }
+}
@@ -32,6 +32,12 @@ pub mod aktool {
         }
     }
 
+    impl log::kv::ToValue for EventId {
+        fn to_value(&self) -> log::kv::Value<'_> {
+            self.0.to_value()
+        }
+    }
+
     #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord)]
     #[serde(transparent)]
     pub struct AKId(u64);
@@ -48,6 +54,12 @@ pub mod aktool {
         }
     }
 
+    impl log::kv::ToValue for AKId {
+        fn to_value(&self) -> log::kv::Value<'_> {
+            self.0.to_value()
+        }
+    }
+
     #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord)]
     #[serde(transparent)]
     pub struct CategoryId(u64);
@@ -241,13 +253,16 @@ use std::{
 pub use aktool::{AKId, CategoryId, EventId, OwnerId};
 use anyhow::{Result, anyhow};
 use itertools::Itertools;
+use regex::Regex;
+
+use serde::{Serialize, Serializer};
 
 use crate::{
     komapedia::{
-        AKSYNC_AK_TEMPLATE, AKSYNC_GENERATED_TEMPLATE, KOMAPEDIA_AK_PREFIX, escape, format_link,
-        is_subpage,
+        KOMAPEDIA_AK_PREFIX, escape, format_link, format_user_link, is_subpage, subpage_title,
+        wikipage,
     },
-    wikipage,
+    users::Users,
 };
 
 #[derive(Debug)]
@@ -258,7 +273,7 @@ pub struct Event {
 }
 
 impl Event {
-    pub(crate) fn new<C, O>(categories: C, owners: O) -> Self
+    pub(crate) fn new<C, O>(categories: C, owners: O, users: &Users) -> Self
     where
         C: IntoIterator<Item = aktool::Category>,
         O: IntoIterator<Item = aktool::Owner>,
@@ -269,11 +284,10 @@ impl Event {
                     .into_iter()
                     .map(|category| (category.id, Category::from(category))),
             ),
-            owners: HashMap::from_iter(
-                owners
-                    .into_iter()
-                    .map(|owner| (owner.id, Owner::from(owner))),
-            ),
+            owners: HashMap::from_iter(owners.into_iter().map(|owner| {
+                let user = users.user(&owner);
+                (owner.id, Owner::from(owner).with_user(user))
+            })),
             aks: HashMap::new(),
         }
     }
@@ -284,6 +298,26 @@ impl Event {
             .sorted_by(|&(id, _), &(other, _)| Ord::cmp(id, other))
     }
 
+    /// The current pages of the KoMa AKs.
+    pub(crate) fn pages(&self, id: EventId) -> Result<HashSet<String>> {
+        self.aks
+            .values()
+            .filter(|ak| ak.is_koma())
+            .map(|ak| ak.wikipage(id))
+            .collect()
+    }
+
+    /// The owners, ordered by their wikitext.
+    pub(crate) fn owners(&self) -> impl Iterator<Item = &Owner> {
+        self.owners
+            .values()
+            .sorted_by_cached_key(|owner| owner.to_string())
+    }
+
+    pub(crate) fn ak(&self, id: &AKId) -> Option<&AK> {
+        self.aks.get(id)
+    }
+
     pub(crate) fn add_ak(&mut self, ak: aktool::AK) -> Result<&mut Self> {
         let category = self
             .categories
@@ -301,7 +335,17 @@ impl Event {
             .collect::<Result<_>>()?;
 
         let id = ak.id;
-        let ak = AK::from_aktool(ak, category.clone(), owners);
+        let mut ak = AK::from_aktool(ak, category.clone(), owners);
+        if ak.is_koma() {
+            let page = ak.page(ak.event, false)?;
+            for other in self.aks.values_mut() {
+                if other.is_koma() && other.id != id && other.page(other.event, false)? == page {
+                    log::warn!(ak = id, page = page.as_str(); "AKs {id} and {} both map to page {page}, appending their ids", other.id);
+                    other.disambiguate = true;
+                    ak.disambiguate = true;
+                }
+            }
+        }
         let _ = self.aks.insert(id, ak);
         Ok(self)
     }
@@ -316,7 +360,59 @@ impl Event {
     }
 }
 
-#[derive(Debug, Clone)]
+/// A subset of the AKs of an event, selected by id, name or category.
+/// An AK is selected if it matches any of the criteria; if there are
+/// no criteria, all AKs are selected.
+#[derive(Debug, Default)]
+pub(crate) struct Selection {
+    ids: HashSet<AKId>,
+    names: Vec<Regex>,
+    categories: Vec<String>,
+}
+
+impl Selection {
+    pub(crate) fn new(ids: &[u64], names: &[String], categories: &[String]) -> Result<Self> {
+        Ok(Self {
+            ids: ids.iter().copied().map(AKId::new).collect(),
+            names: names
+                .iter()
+                .map(|name| Regex::new(name))
+                .collect::<Result<_, _>>()?,
+            categories: categories.to_vec(),
+        })
+    }
+
+    pub(crate) fn is_all(&self) -> bool {
+        self.ids.is_empty() && self.names.is_empty() && self.categories.is_empty()
+    }
+
+    pub(crate) fn contains(&self, ak: &AK) -> bool {
+        // match the names as given in aktool, too, not only with the
+        // prefix we add
+        let names = [&ak.name, &ak.short_name]
+            .into_iter()
+            .flat_map(|name| {
+                [
+                    name.as_str(),
+                    name.strip_prefix(KOMAPEDIA_AK_PREFIX).unwrap_or(name),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        self.is_all()
+            || self.ids.contains(&ak.id)
+            || self
+                .names
+                .iter()
+                .any(|pattern| names.iter().any(|name| pattern.is_match(name)))
+            || self
+                .categories
+                .iter()
+                .any(|category| category.eq_ignore_ascii_case(&ak.category.name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Category {
     name: String,
     description: String,
@@ -342,6 +438,14 @@ pub struct Owner {
     name: String,
     institution: Option<String>,
     link: Option<String>,
+    /// The name of the owner on KoMapedia.
+    user: Option<String>,
+}
+
+impl Owner {
+    fn with_user(self, user: Option<String>) -> Self {
+        Self { user, ..self }
+    }
 }
 
 impl From<aktool::Owner> for Owner {
@@ -350,13 +454,17 @@ impl From<aktool::Owner> for Owner {
             name: value.name,
             institution: (!value.institution.is_empty()).then_some(value.institution),
             link: (!value.link.is_empty()).then_some(value.link),
+            user: None,
         }
     }
 }
 
 impl Display for Owner {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let link = format_link(escape(&self.name), &self.link);
+        let link = match &self.user {
+            Some(user) => format_user_link(escape(&self.name), user),
+            None => format_link(escape(&self.name), &self.link),
+        };
         match &self.institution {
             None => write!(f, "{link}"),
             Some(institution) => write!(f, "{link} ({})", escape(institution)),
@@ -371,7 +479,6 @@ pub struct AK {
     description: String,
     result: String,
     owners: HashSet<Owner>,
-    #[allow(unused)]
     category: Category,
     duration: f64,
 
@@ -385,6 +492,8 @@ pub struct AK {
     koma: bool,
     event: EventId,
     id: AKId,
+    /// Whether another AK of the event has the same page title.
+    disambiguate: bool,
 }
 
 impl AK {
@@ -424,6 +533,7 @@ impl AK {
             koma,
             event: ak.event,
             id: ak.id,
+            disambiguate: false,
         }
     }
 
@@ -431,19 +541,23 @@ impl AK {
         self.koma
     }
 
+    pub(crate) fn has_owner(&self, owner: &Owner) -> bool {
+        self.owners.contains(owner)
+    }
+
     pub(crate) fn wikipage(&self, event: EventId) -> Result<String> {
-        Ok(format!(
-            "{}/{}",
-            wikipage(event)?,
-            self.short_name
-                .replace(' ', "_")
-                .replace('[', "(")
-                .replace(']', ")")
-        ))
+        self.page(event, self.disambiguate)
     }
 
-    pub(crate) fn wikitext(&self) -> String {
-        format!("{{{{{AKSYNC_GENERATED_TEMPLATE}}}}}\n{self}")
+    /// The page title, with the id appended if `disambiguate` is set.
+    fn page(&self, event: EventId, disambiguate: bool) -> Result<String> {
+        let suffix = if disambiguate {
+            format!("_({})", self.id)
+        } else {
+            String::new()
+        };
+
+        Ok(subpage_title(&wikipage(event)?, &self.short_name, &suffix))
     }
 
     pub(crate) fn name(&self) -> &str {
@@ -458,28 +572,17 @@ impl AK {
         format!("[[Aktool event::{event}]] [[Aktool id::{}]]", self.id)
     }
 
-    fn format_type(&self) -> String {
-        Itertools::intersperse(
-            [
-                (self.exchange, "Austausch"),
-                (self.input, "Input"),
-                (self.output, "Output"),
-                (self.reso, "Reso"),
-                (self.talk, "Vortrag"),
-                (self.fun, "Spaß"),
-            ]
-            .into_iter()
-            .filter_map(|(predicate, value)| if predicate { Some(value) } else { None }),
-            ",",
-        )
-        .collect()
-    }
-
-    fn format_owners(&self) -> String {
-        Itertools::intersperse(
-            self.owners.iter().map(|owner| owner.to_string()).sorted(),
-            ", ".to_string(),
-        )
+    fn types(&self) -> Vec<&'static str> {
+        [
+            (self.exchange, "Austausch"),
+            (self.input, "Input"),
+            (self.output, "Output"),
+            (self.reso, "Reso"),
+            (self.talk, "Vortrag"),
+            (self.fun, "Spaß"),
+        ]
+        .into_iter()
+        .filter_map(|(predicate, value)| predicate.then_some(value))
         .collect()
     }
 
@@ -489,43 +592,182 @@ impl AK {
             Some(subpage) => format!("{{{{/{subpage}}}}}"),
         }
     }
-}
 
-impl Display for AK {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        macro_rules! attribute {
-            ($key:expr=>$value:expr) => {
-                writeln!(f, "|{}={}", $key, $value)?;
-            };
-        }
-
-        writeln!(f, "{{{{{AKSYNC_AK_TEMPLATE}")?;
+    /// The data available to the template for the page of the AK.
+    pub(crate) fn context(&self) -> Result<AKContext<'_>> {
+        Ok(AKContext {
+            id: self.id,
+            event: self.event,
+            page: self.wikipage(self.event)?,
+            name: &self.name,
+            short_name: &self.short_name,
+            description: &self.description,
+            result: &self.result,
+            result_wikitext: self.format_result(self.event),
+            category: &self.category,
+            owners: self
+                .owners
+                .iter()
+                .map(|owner| OwnerContext {
+                    name: &owner.name,
+                    institution: owner.institution.as_deref(),
+                    link: owner.link.as_deref(),
+                    user: owner.user.as_deref(),
+                    wikitext: owner.to_string(),
+                })
+                .sorted_by(|left, right| left.wikitext.cmp(&right.wikitext))
+                .collect(),
+            duration: self.duration,
+            types: self.types(),
+            exchange: self.exchange,
+            input: self.input,
+            output: self.output,
+            reso: self.reso,
+            talk: self.talk,
+            fun: self.fun,
+            koma: self.koma,
+        })
+    }
+}
 
-        attribute!("Name" => escape(&self.name));
+/// An AK as seen by the page template.
+#[derive(Debug, Serialize)]
+pub(crate) struct AKContext<'a> {
+    id: AKId,
+    event: EventId,
+    page: String,
+    name: &'a str,
+    short_name: &'a str,
+    description: &'a str,
+    result: &'a str,
+    /// The result as wikitext, transcluding it if it is a subpage.
+    result_wikitext: String,
+    category: &'a Category,
+    /// The owners, ordered by their wikitext.
+    owners: Vec<OwnerContext<'a>>,
+    #[serde(serialize_with = "serialize_duration")]
+    duration: f64,
+    types: Vec<&'static str>,
+    exchange: bool,
+    input: bool,
+    output: bool,
+    reso: bool,
+    talk: bool,
+    fun: bool,
+    koma: bool,
+}
 
-        let types = self.format_type();
-        if !types.is_empty() {
-            attribute!("Typ" => self.format_type());
-        }
+#[derive(Debug, Serialize)]
+struct OwnerContext<'a> {
+    name: &'a str,
+    institution: Option<&'a str>,
+    link: Option<&'a str>,
+    /// The name of the owner on KoMapedia.
+    user: Option<&'a str>,
+    /// A link to the owner, with their institution.
+    wikitext: String,
+}
 
-        let owners = self.format_owners();
-        if !owners.is_empty() {
-            attribute!("Leitung" => self.format_owners());
-        }
+/// Serialise whole durations as integers, so they render as `2` instead
+/// of `2.0`.
+fn serialize_duration<S: Serializer>(duration: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    if duration.fract() == 0.0 {
+        serializer.serialize_i64(*duration as i64)
+    } else {
+        serializer.serialize_f64(*duration)
+    }
+}
 
-        attribute!("Dauer" => self.duration);
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use test_log::test;
+
+    use super::{AK, Event, Selection, aktool};
+    use crate::users::Users;
+
+    fn ak() -> AK {
+        AK::from_aktool(
+            serde_json::from_str::<aktool::AK>(
+                r#"{"id":1305,"name":"IT-Infrastruktur","short_name":"IT-Infrastruktur","description":"Test","link":"","protocol_link":"","reso":false,"present":null,"notes":"","interest":-1,"interest_counter":0,"include_in_export":true,"category":64,"track":null,"event":16,"owners":[],"types":[2],"requirements":[],"conflicts":[],"prerequisites":[]}"#,
+            )
+            .unwrap(),
+            serde_json::from_str::<aktool::Category>(
+                r##"{"id":64,"name":"Inhalt/Arbeit","color":"#487eb0","description":"","present_by_default":false,"event":16}"##,
+            )
+            .unwrap()
+            .into(),
+            HashSet::new(),
+        )
+    }
 
-        if !self.description.is_empty() {
-            attribute!("Beschreibung" => escape(&self.description));
-        }
+    #[test]
+    fn selection() {
+        let ak = ak();
+
+        assert!(Selection::default().contains(&ak));
+        assert!(Selection::new(&[1305], &[], &[]).unwrap().contains(&ak));
+        assert!(!Selection::new(&[1306], &[], &[]).unwrap().contains(&ak));
+        assert!(
+            Selection::new(&[], &["Infra".to_string()], &[])
+                .unwrap()
+                .contains(&ak)
+        );
+        assert!(
+            Selection::new(&[], &["^IT-".to_string()], &[])
+                .unwrap()
+                .contains(&ak)
+        );
+        assert!(
+            Selection::new(&[], &["^AK IT-".to_string()], &[])
+                .unwrap()
+                .contains(&ak)
+        );
+        assert!(
+            Selection::new(&[1306], &[], &["inhalt/arbeit".to_string()])
+                .unwrap()
+                .contains(&ak)
+        );
+        assert!(
+            !Selection::new(&[], &["^Infra".to_string()], &["Kultur".to_string()])
+                .unwrap()
+                .contains(&ak)
+        );
+    }
 
-        if !self.result.is_empty() {
-            attribute!("Ergebnis" => self.format_result(self.event));
+    #[test]
+    fn colliding_pages() {
+        let mut event = Event::new(
+            [serde_json::from_str::<aktool::Category>(
+                r##"{"id":64,"name":"Inhalt/Arbeit","color":"#487eb0","description":"","present_by_default":false,"event":16}"##,
+            )
+            .unwrap()],
+            [],
+            &Users::default(),
+        );
+        for (id, short_name) in [(1305, "IT [Infra]"), (1306, "IT (Infra)."), (1307, "IT")] {
+            event
+                .add_ak(
+                    serde_json::from_str::<aktool::AK>(&format!(
+                        r#"{{"id":{id},"name":"IT","short_name":"{short_name}","description":"","link":"","protocol_link":"","reso":false,"present":null,"notes":"","interest":-1,"interest_counter":0,"include_in_export":true,"category":64,"track":null,"event":16,"owners":[],"types":[2],"requirements":[],"conflicts":[],"prerequisites":[]}}"#
+                    ))
+                    .unwrap(),
+                )
+                .unwrap();
         }
 
-        attribute!("Event" => self.event);
-        attribute!("ID" => self.id);
-
-        writeln!(f, "}}}}")
+        let pages = event
+            .aks()
+            .map(|(_, ak)| ak.wikipage(aktool::EVENT_KOMA92).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pages,
+            [
+                "KoMa_92/AK_IT_(Infra)_(1305)",
+                "KoMa_92/AK_IT_(Infra)_(1306)",
+                "KoMa_92/AK_IT"
+            ]
+        );
     }
 }
@@ -0,0 +1,261 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use anyhow::{Result, bail};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+};
+use reqwest::Client;
+use serde_json::json;
+
+use crate::{
+    AKSYNC_USER_AGENT,
+    config::{EmailConfig, NotifyConfig, WebhookConfig, secret_from_env},
+    report::Report,
+};
+
+/// Whether someone should look at the outcome of the run. Pages that
+/// were already skipped in the previous run have been reported before.
+fn needs_attention(config: &NotifyConfig, report: &Report) -> bool {
+    !report.is_success()
+        || report.newly_skipped > 0
+        || report.deleted.len() > config.deletion_threshold
+}
+
+/// Send the report to all configured destinations, if it needs
+/// attention. A failing destination does not keep the others from
+/// being notified.
+pub(crate) async fn notify(config: &NotifyConfig, report: &Report) -> Result<()> {
+    if !needs_attention(config, report) {
+        return Ok(());
+    }
+
+    let mut errors = Vec::new();
+    if let Some(email) = &config.email
+        && let Err(err) = send_email(email, report).await
+    {
+        errors.push(format!("failed to send notification email: {err:#}"));
+    }
+
+    if let Some(webhook) = &config.webhook
+        && let Err(err) = send_webhook(webhook, report).await
+    {
+        errors.push(format!("failed to send notification webhook: {err:#}"));
+    }
+
+    if !errors.is_empty() {
+        bail!(errors.join("; "));
+    }
+
+    Ok(())
+}
+
+async fn send_email(config: &EmailConfig, report: &Report) -> Result<()> {
+    let mut transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(&config.server)?;
+    if let Some(username) = &config.username {
+        transport = transport.credentials(Credentials::new(
+            username.clone(),
+            secret_from_env("AKSYNC_SMTP_PASSWORD")?,
+        ));
+    }
+
+    let mut message = Message::builder()
+        .from(config.from.parse()?)
+        .subject(report.summary());
+    for to in &config.to {
+        message = message.to(to.parse()?);
+    }
+
+    transport
+        .build()
+        .send(
+            message
+                .header(ContentType::TEXT_PLAIN)
+                .body(report.to_string())?,
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn send_webhook(config: &WebhookConfig, report: &Report) -> Result<()> {
+    Client::builder()
+        .user_agent(AKSYNC_USER_AGENT)
+        .build()?
+        .post(&config.url)
+        .json(&json!({ "text": report.to_string(), "report": report }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use axum::{Json, Router, routing::post};
+    use serde_json::Value;
+    use test_log::test;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        sync::mpsc,
+    };
+
+    use super::{needs_attention, notify};
+    use crate::{
+        config::{EmailConfig, NotifyConfig, WebhookConfig},
+        http::serve,
+        report::Report,
+    };
+
+    fn report() -> Report {
+        Report {
+            synced: vec!["KoMa_92/AK_Testwurst".to_string()],
+            skipped: vec![(
+                "KoMa_92/AK_Wurst".to_string(),
+                "got error editconflict: Edit conflict.".to_string(),
+            )],
+            newly_skipped: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn attention() {
+        let config = NotifyConfig::default();
+        let mut report = Report::default();
+        assert!(!needs_attention(&config, &report));
+
+        report.skipped = vec![(
+            "KoMa_92/AK_Wurst".to_string(),
+            "got error protectedpage: Geschützt.".to_string(),
+        )];
+        assert!(!needs_attention(&config, &report));
+        report.newly_skipped = 1;
+        assert!(needs_attention(&config, &report));
+        report.newly_skipped = 0;
+
+        report.deleted = vec!["KoMa_92/AK_Alt".to_string(); config.deletion_threshold];
+        assert!(!needs_attention(&config, &report));
+        report.deleted.push("KoMa_92/AK_Uralt".to_string());
+        assert!(needs_attention(&config, &report));
+
+        let report = Report {
+            error: Some("got HTTP status 500".to_string()),
+            ..Default::default()
+        };
+        assert!(needs_attention(&config, &report));
+    }
+
+    /// Serve a webhook, passing on the bodies it receives.
+    async fn webhook_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<Value>| async move {
+                sender.send(body).unwrap();
+            }),
+        );
+
+        (format!("{}/hook", serve(app).await), receiver)
+    }
+
+    #[test(tokio::test)]
+    async fn failing_email() {
+        let (url, mut receiver) = webhook_server().await;
+
+        // nobody listens here once the listener is dropped
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let smtp = closed.local_addr().unwrap();
+        drop(closed);
+
+        let config = NotifyConfig {
+            email: Some(EmailConfig {
+                server: format!("smtp://{smtp}"),
+                username: None,
+                from: "aksync@example.org".to_string(),
+                to: vec!["orga@example.org".to_string()],
+            }),
+            webhook: Some(WebhookConfig { url }),
+            ..Default::default()
+        };
+        let result = notify(&config, &report()).await;
+
+        assert!(receiver.recv().await.is_some());
+        assert!(format!("{:#}", result.unwrap_err()).contains("failed to send notification email"));
+    }
+
+    #[test(tokio::test)]
+    async fn webhook() {
+        let (url, mut receiver) = webhook_server().await;
+
+        let config = NotifyConfig {
+            webhook: Some(WebhookConfig { url }),
+            ..Default::default()
+        };
+        notify(&config, &report()).await.unwrap();
+
+        let body = receiver.recv().await.unwrap();
+        let text = body["text"].as_str().unwrap();
+        assert!(text.contains("* KoMa_92/AK_Wurst: got error editconflict"));
+        assert_eq!(body["report"]["synced"][0], "KoMa_92/AK_Testwurst");
+    }
+
+    /// Accept a single message, just enough SMTP for lettre.
+    async fn smtp_server(listener: TcpListener) -> String {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut data = None::<String>;
+
+        writer.write_all(b"220 localhost\r\n").await.unwrap();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let reply: &[u8] = match &mut data {
+                Some(message) if line == "." => {
+                    let _ = writer.write_all(b"250 queued\r\n").await;
+                    return message.clone();
+                }
+                Some(message) => {
+                    message.push_str(&line);
+                    message.push('\n');
+                    continue;
+                }
+                None if line.starts_with("DATA") => {
+                    data = Some(String::new());
+                    b"354 go ahead\r\n"
+                }
+                None => b"250 ok\r\n",
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+
+        panic!("connection closed before the message was sent");
+    }
+
+    #[test(tokio::test)]
+    async fn email() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(smtp_server(listener));
+
+        let config = NotifyConfig {
+            email: Some(EmailConfig {
+                server: format!("smtp://{address}"),
+                username: None,
+                from: "aksync@example.org".to_string(),
+                to: vec!["orga@example.org".to_string()],
+            }),
+            ..Default::default()
+        };
+        notify(&config, &report()).await.unwrap();
+
+        let message = server.await.unwrap();
+        assert!(message.contains("Subject: aksync run finished"));
+        assert!(message.contains("To: orga@example.org"));
+        assert!(message.contains("* KoMa_92/AK_Wurst: got error editconflict"));
+    }
+}
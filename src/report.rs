@@ -0,0 +1,91 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::fmt::{self, Display};
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// What happened to the pages on KoMapedia during a sync run.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Report {
+    /// Pages that were saved.
+    pub(crate) synced: Vec<String>,
+    /// Number of pages that were already up to date.
+    pub(crate) unchanged: usize,
+    /// Pages that were deleted.
+    pub(crate) deleted: Vec<String>,
+    /// Pages that were not saved, with the reason.
+    pub(crate) skipped: Vec<(String, String)>,
+    /// Number of skipped pages that were not skipped in the previous run.
+    #[serde(skip)]
+    pub(crate) newly_skipped: usize,
+    /// Pages that could not be saved, with the error.
+    pub(crate) failed: Vec<(String, String)>,
+    /// The error that aborted the run, if any.
+    pub(crate) error: Option<String>,
+}
+
+impl Report {
+    /// Record the outcome of the run.
+    pub(crate) fn finish<T>(&mut self, result: &Result<T>) {
+        self.error = result.as_ref().err().map(|err| format!("{err:#}"));
+    }
+
+    /// Whether nothing happened worth mentioning, i.e., all pages were
+    /// already up to date.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.error.is_none()
+            && self.synced.is_empty()
+            && self.deleted.is_empty()
+            && self.skipped.is_empty()
+            && self.failed.is_empty()
+    }
+
+    pub(crate) fn is_success(&self) -> bool {
+        self.error.is_none() && self.failed.is_empty()
+    }
+
+    pub(crate) fn summary(&self) -> &'static str {
+        if self.is_success() {
+            "aksync run finished"
+        } else {
+            "aksync run failed"
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            Some(error) => writeln!(f, "{}: {error}", self.summary())?,
+            None => writeln!(f, "{}.", self.summary())?,
+        }
+        writeln!(
+            f,
+            "Synchronised {} pages, {} were unchanged.",
+            self.synced.len(),
+            self.unchanged
+        )?;
+
+        if !self.deleted.is_empty() {
+            writeln!(f, "\nDeleted {} pages:", self.deleted.len())?;
+            for page in &self.deleted {
+                writeln!(f, "* {page}")?;
+            }
+        }
+
+        for (title, pages) in [("Skipped", &self.skipped), ("Failed", &self.failed)] {
+            if !pages.is_empty() {
+                writeln!(f, "\n{title} {} pages:", pages.len())?;
+                for (page, reason) in pages {
+                    writeln!(f, "* {page}: {reason}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use axum::{
+    Router,
+    extract::State,
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::IntoResponse,
+    routing::{get, post},
+};
+use tokio::{net::TcpListener, sync::Notify, time::timeout};
+
+use crate::{cli::SyncArgs, commands, config::Config, metrics::METRICS};
+
+/// Run as a daemon, synchronising whenever someone sends a POST request
+/// to `/sync`. Requests arriving in quick succession are debounced into
+/// a single run, and runs never overlap. Metrics are served on `/metrics`.
+pub(crate) async fn serve(config: &Config, args: &SyncArgs) -> Result<()> {
+    let trigger = Arc::new(Notify::new());
+    let app = Router::new()
+        .route("/sync", post(request_sync))
+        .route("/metrics", get(metrics))
+        .with_state(trigger.clone());
+    let listener = TcpListener::bind(config.serve.listen).await?;
+
+    log::info!("listening on {}", config.serve.listen);
+
+    tokio::select! {
+        result = axum::serve(listener, app).into_future() => Ok(result?),
+        result = run(config, args, &trigger) => result,
+    }
+}
+
+async fn request_sync(State(trigger): State<Arc<Notify>>) -> StatusCode {
+    log::info!("sync requested");
+    trigger.notify_one();
+
+    StatusCode::ACCEPTED
+}
+
+async fn metrics() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        METRICS.render(),
+    )
+}
+
+async fn run(config: &Config, args: &SyncArgs, trigger: &Notify) -> Result<()> {
+    let debounce = Duration::from_secs(config.serve.debounce);
+
+    // synchronise once on startup
+    trigger.notify_one();
+
+    loop {
+        match config.serve.interval {
+            Some(interval) => {
+                let _ = timeout(Duration::from_secs(interval), trigger.notified()).await;
+            }
+            None => trigger.notified().await,
+        }
+
+        while timeout(debounce, trigger.notified()).await.is_ok() {
+            log::debug!("sync requested again, waiting another {debounce:?}");
+        }
+
+        // requests arriving while we sync are remembered by `trigger`
+        // and lead to another run afterwards
+        if let Err(err) = commands::sync(config, args).await {
+            log::error!("sync failed: {err:#}");
+        }
+    }
+}
@@ -0,0 +1,311 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::model::{AKId, EventId};
+
+/// What we know about the previous runs, persisted between runs to
+/// avoid touching AKs that have not changed.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct State {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    force: bool,
+    /// Time of the last successful run.
+    #[serde(default)]
+    last_success: Option<u64>,
+    #[serde(default)]
+    events: BTreeMap<EventId, BTreeMap<AKId, AKState>>,
+    /// AKs whose page was skipped in the last run.
+    #[serde(default)]
+    skipped: BTreeMap<EventId, BTreeSet<AKId>>,
+}
+
+/// The state of an AK page after it was last synchronised.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct AKState {
+    /// Title of the page of the AK.
+    #[serde(default)]
+    pub(crate) page: Option<String>,
+    /// Id of the revision we saved last.
+    #[serde(default)]
+    pub(crate) revision: Option<u64>,
+    /// Hash of the page title and the wikitext.
+    pub(crate) hash: String,
+    /// When the page was last synchronised, in seconds since the epoch.
+    pub(crate) synced: u64,
+}
+
+impl State {
+    /// Load the state from `path`, starting afresh if the file does not
+    /// exist. Without a `path`, the state is not persisted.
+    pub(crate) fn load(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+
+        let mut state = if path.exists() {
+            serde_json::from_str::<Self>(
+                &fs::read_to_string(path)
+                    .with_context(|| format!("failed to read state file {path:?}"))?,
+            )
+            .with_context(|| format!("invalid state file {path:?}"))?
+        } else {
+            Self::default()
+        };
+        state.path = Some(path.to_path_buf());
+
+        Ok(state)
+    }
+
+    /// Treat all AKs as changed, while still recording their new state.
+    pub(crate) fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        write_atomically(path, &serde_json::to_string_pretty(self)?, "state file")
+    }
+
+    /// Whether the AK was last synchronised with the same content.
+    pub(crate) fn is_current(&self, event: EventId, ak: AKId, hash: &str) -> bool {
+        !self.force
+            && self
+                .events
+                .get(&event)
+                .and_then(|aks| aks.get(&ak))
+                .is_some_and(|state| state.hash == hash)
+    }
+
+    pub(crate) fn get(&self, event: EventId, ak: AKId) -> Option<&AKState> {
+        self.events.get(&event).and_then(|aks| aks.get(&ak))
+    }
+
+    /// The revision to base an edit of `page` on, so that the edit is
+    /// rejected if someone else changed the page since we last saved it.
+    pub(crate) fn base_revision(&self, event: EventId, ak: AKId, page: &str) -> Option<u64> {
+        self.get(event, ak)
+            .filter(|state| !self.force && state.page.as_deref() == Some(page))
+            .and_then(|state| state.revision)
+    }
+
+    /// Forget the revision we saved last after an edit conflict, so
+    /// that the next run does not base its edit on it again.
+    pub(crate) fn conflict(&mut self, event: EventId, ak: AKId) {
+        if let Some(state) = self.events.get_mut(&event).and_then(|aks| aks.get_mut(&ak)) {
+            state.revision = None;
+        }
+    }
+
+    /// Record that saving the page of the AK was skipped. Returns
+    /// whether it was not already skipped in the previous run.
+    pub(crate) fn skip(&mut self, event: EventId, ak: AKId) -> bool {
+        self.skipped.entry(event).or_default().insert(ak)
+    }
+
+    /// Record that the AK was saved as `page`, keeping the previous
+    /// revision id if the new one is unknown, e.g., for null edits.
+    pub(crate) fn record(
+        &mut self,
+        event: EventId,
+        ak: AKId,
+        page: String,
+        revision: Option<u64>,
+        hash: String,
+    ) {
+        let synced = now();
+        let revision = revision.or_else(|| {
+            self.get(event, ak)
+                .filter(|state| state.page.as_ref() == Some(&page))
+                .and_then(|state| state.revision)
+        });
+
+        if let Some(skipped) = self.skipped.get_mut(&event) {
+            skipped.remove(&ak);
+        }
+        self.events.entry(event).or_default().insert(
+            ak,
+            AKState {
+                page: Some(page),
+                revision,
+                hash,
+                synced,
+            },
+        );
+    }
+
+    pub(crate) fn last_success(&self) -> Option<u64> {
+        self.last_success
+    }
+
+    /// Remember that the current run succeeded.
+    pub(crate) fn succeeded(&mut self) {
+        self.last_success = Some(now());
+    }
+
+    /// Pages of AKs of `event` that no longer exist.
+    pub(crate) fn removed(&self, event: EventId, aks: &HashSet<AKId>) -> Vec<String> {
+        self.events
+            .get(&event)
+            .into_iter()
+            .flatten()
+            .filter(|(ak, _)| !aks.contains(ak))
+            .filter_map(|(_, state)| state.page.clone())
+            .collect()
+    }
+
+    /// Forget about AKs of `event` that no longer exist.
+    pub(crate) fn retain(&mut self, event: EventId, aks: &HashSet<AKId>) {
+        if let Some(state) = self.events.get_mut(&event) {
+            state.retain(|ak, _| aks.contains(ak));
+        }
+        if let Some(skipped) = self.skipped.get_mut(&event) {
+            skipped.retain(|ak| aks.contains(ak));
+        }
+    }
+}
+
+/// Replace the `what` at `path` with `contents`. We write to a
+/// temporary file first and rename it, so that readers never see a
+/// truncated file.
+pub(crate) fn write_atomically(path: &Path, contents: &str, what: &str) -> Result<()> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, contents)
+        .with_context(|| format!("failed to write {what} {temporary:?}"))?;
+    fs::rename(&temporary, path).with_context(|| format!("failed to write {what} {path:?}"))
+}
+
+/// The current time, in seconds since the epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Hash of the title and content of a page, for detecting changes.
+pub(crate) fn content_hash(page: &str, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(page.as_bytes());
+    hasher.update([0]);
+    hasher.update(text.as_bytes());
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use test_log::test;
+
+    use super::{State, content_hash};
+    use crate::model::{AKId, aktool::EVENT_KOMA92};
+
+    const PAGE: &str = "KoMa_92/AK_Testwurst";
+
+    #[test]
+    fn changes() {
+        let ak = AKId::new(1289);
+        let hash = content_hash(PAGE, "{{AK}}");
+        let mut state = State::default();
+
+        assert!(!state.is_current(EVENT_KOMA92, ak, &hash));
+        state.record(EVENT_KOMA92, ak, PAGE.to_string(), Some(42), hash.clone());
+        assert!(state.is_current(EVENT_KOMA92, ak, &hash));
+
+        // null edits keep the revision
+        state.record(EVENT_KOMA92, ak, PAGE.to_string(), None, hash.clone());
+        assert_eq!(state.get(EVENT_KOMA92, ak).unwrap().revision, Some(42));
+        assert!(!state.is_current(
+            EVENT_KOMA92,
+            ak,
+            &content_hash("KoMa_92/AK_Testwurst2", "{{AK}}")
+        ));
+
+        let state = state.force(true);
+        assert!(!state.is_current(EVENT_KOMA92, ak, &hash));
+    }
+
+    #[test]
+    fn conflict() {
+        let ak = AKId::new(1289);
+        let hash = content_hash(PAGE, "{{AK}}");
+        let mut state = State::default();
+
+        assert_eq!(state.base_revision(EVENT_KOMA92, ak, PAGE), None);
+        state.record(EVENT_KOMA92, ak, PAGE.to_string(), Some(42), hash.clone());
+        assert_eq!(state.base_revision(EVENT_KOMA92, ak, PAGE), Some(42));
+        assert_eq!(
+            state.base_revision(EVENT_KOMA92, ak, "KoMa_92/AK_Testwurst2"),
+            None
+        );
+
+        // the next run must not run into the same conflict
+        state.conflict(EVENT_KOMA92, ak);
+        assert_eq!(state.base_revision(EVENT_KOMA92, ak, PAGE), None);
+        assert!(state.is_current(EVENT_KOMA92, ak, &hash));
+
+        // only the first skip is news
+        assert!(state.skip(EVENT_KOMA92, ak));
+        assert!(!state.skip(EVENT_KOMA92, ak));
+
+        state.record(EVENT_KOMA92, ak, PAGE.to_string(), Some(45), hash);
+        assert_eq!(state.base_revision(EVENT_KOMA92, ak, PAGE), Some(45));
+        assert!(state.skip(EVENT_KOMA92, ak));
+        let state = state.force(true);
+        assert_eq!(state.base_revision(EVENT_KOMA92, ak, PAGE), None);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let ak = AKId::new(1289);
+        let hash = content_hash(PAGE, "{{AK}}");
+        let mut state = State::default();
+        state.record(EVENT_KOMA92, ak, PAGE.to_string(), Some(42), hash.clone());
+        state.record(
+            EVENT_KOMA92,
+            AKId::new(1290),
+            "KoMa_92/AK_Zwei".to_string(),
+            Some(43),
+            hash.clone(),
+        );
+
+        let aks = HashSet::from([ak]);
+        assert_eq!(
+            state.removed(EVENT_KOMA92, &aks),
+            vec!["KoMa_92/AK_Zwei".to_string()]
+        );
+        state.retain(EVENT_KOMA92, &aks);
+
+        let state = serde_json::from_str::<State>(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert!(state.is_current(EVENT_KOMA92, ak, &hash));
+        assert!(!state.is_current(EVENT_KOMA92, AKId::new(1290), &hash));
+    }
+}
@@ -0,0 +1,146 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::fs::read_to_string;
+
+use anyhow::{Context, Result};
+use minijinja::{Environment, UndefinedBehavior, syntax::SyntaxConfig};
+
+use crate::{
+    config::KoMapediaConfig,
+    komapedia::{AKSYNC_GENERATED_TEMPLATE, escape, escape_markup},
+    model::AK,
+};
+
+/// The template used unless the configuration names another one.
+const DEFAULT_TEMPLATE: &str = include_str!("../templates/ak.wiki");
+
+const TEMPLATE_NAME: &str = "ak";
+
+/// The template for the wikitext of AK pages.
+///
+/// Since wikitext is full of `{{`, the template uses `<< … >>` for
+/// expressions, `<% … %>` for statements and `<# … #>` for comments.
+pub(crate) struct Template {
+    env: Environment<'static>,
+}
+
+impl Template {
+    /// Load the configured template, or use the default template.
+    pub(crate) fn load(config: &KoMapediaConfig) -> Result<Self> {
+        let path = config.template.as_deref();
+        let source = match path {
+            None => DEFAULT_TEMPLATE.to_string(),
+            Some(path) => {
+                read_to_string(path).with_context(|| format!("failed to read template {path:?}"))?
+            }
+        };
+
+        let mut env = Environment::new();
+        env.set_syntax(
+            SyntaxConfig::builder()
+                .block_delimiters("<%", "%>")
+                .variable_delimiters("<<", ">>")
+                .comment_delimiters("<#", "#>")
+                .build()?,
+        );
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.set_keep_trailing_newline(true);
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.add_global("generated_template", AKSYNC_GENERATED_TEMPLATE);
+        env.add_filter("wiki_escape", |text: String| escape(&text));
+        let markup = config.markup;
+        env.add_filter("wiki_markup", move |text: String| {
+            escape_markup(&text, markup)
+        });
+        env.add_template_owned(TEMPLATE_NAME, source)
+            .with_context(|| format!("invalid template {path:?}"))?;
+
+        Ok(Self { env })
+    }
+
+    /// The wikitext for the page of `ak`.
+    pub(crate) fn render(&self, ak: &AK) -> Result<String> {
+        self.env
+            .get_template(TEMPLATE_NAME)?
+            .render(ak.context()?)
+            .with_context(|| format!("failed to render template for {}", ak.name()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use test_log::test;
+
+    use super::Template;
+    use crate::model::{AK, aktool};
+
+    fn from_json(ak: &str, owners: &[&str]) -> AK {
+        AK::from_aktool(
+            serde_json::from_str::<aktool::AK>(ak).unwrap(),
+            serde_json::from_str::<aktool::Category>(
+                r##"{"id":64,"name":"Inhalt/Arbeit","color":"#487eb0","description":"","present_by_default":false,"event":16}"##,
+            )
+            .unwrap()
+            .into(),
+            owners
+                .iter()
+                .map(|owner| {
+                    serde_json::from_str::<aktool::Owner>(owner)
+                        .unwrap()
+                        .into()
+                })
+                .collect::<HashSet<_>>(),
+        )
+    }
+
+    #[test]
+    fn default_template() {
+        let template = Template::load(&Default::default()).unwrap();
+
+        let ak = from_json(
+            r#"{"id":1305,"name":"IT-Infrastruktur","short_name":"IT","description":"Wir {{reden}} über a=b","link":"","protocol_link":"https://de.komapedia.org/wiki/KoMa_92/AK_IT/Ergebnis","reso":true,"present":null,"notes":"","interest":-1,"interest_counter":0,"include_in_export":true,"category":64,"track":null,"event":16,"owners":[1312,1313],"types":[1,2],"requirements":[],"conflicts":[],"prerequisites":[]}"#,
+            &[
+                r#"{"id":1312,"name":"mmarx","slug":"mmarx","institution":"TU Dresden","link":"https://de.komapedia.org/wiki/Benutzer:Mmarx","event":16}"#,
+                r#"{"id":1313,"name":"Anna|Lena","slug":"al","institution":"","link":"","event":16}"#,
+            ],
+        );
+        assert_eq!(
+            template.render(&ak).unwrap(),
+            r#"{{Seite automatisch erzeugt von aksync}}
+{{KoMa Externer AK aus aktool
+|Name=AK IT-Infrastruktur
+|Typ=Input,Output,Reso
+|Leitung=Anna&#124;Lena, [[Benutzer:Mmarx|mmarx]] (TU Dresden)
+|Dauer=0
+|Beschreibung=Wir &#123;&#123;reden&#125;&#125; über a&#61;b
+|Ergebnis={{/Ergebnis}}
+|Event=16
+|ID=1305
+}}
+"#
+        );
+
+        let ak = from_json(
+            r#"{"id":1305,"name":"IT-Infrastruktur","short_name":"IT-Infrastruktur","description":"","link":"","protocol_link":"","reso":false,"present":null,"notes":"","interest":-1,"interest_counter":0,"include_in_export":true,"category":64,"track":null,"event":16,"owners":[],"types":[],"requirements":[],"conflicts":[],"prerequisites":[]}"#,
+            &[],
+        );
+        assert_eq!(
+            template.render(&ak).unwrap(),
+            r#"{{Seite automatisch erzeugt von aksync}}
+{{KoMa Externer AK aus aktool
+|Name=AK IT-Infrastruktur
+|Typ=Input,Output
+|Dauer=0
+|Event=16
+|ID=1305
+}}
+"#
+        );
+    }
+}
@@ -0,0 +1,77 @@
+// © 2025 Maximilian Marx
+// SPDX-FileContributor: Maximilian Marx
+//
+// SPDX-License-Identifier: EUPL-1.2
+
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    komapedia::{user_name, user_page},
+    model::aktool,
+};
+
+/// Maps aktool owners to KoMapedia users.
+///
+/// The mapping file is a TOML table from the slug or name of an owner
+/// to a user name, e.g., `mmarx = "Mmarx"`. Owners not in the mapping
+/// are linked to the user whose user page they give as their link.
+#[derive(Debug, Default)]
+pub(crate) struct Users(HashMap<String, String>);
+
+impl Users {
+    pub(crate) fn load(path: Option<&Path>) -> Result<Self> {
+        match path {
+            None => Ok(Self::default()),
+            Some(path) => Ok(Self(
+                toml::from_str(
+                    &read_to_string(path)
+                        .with_context(|| format!("failed to read user mapping {path:?}"))?,
+                )
+                .with_context(|| format!("invalid user mapping {path:?}"))?,
+            )),
+        }
+    }
+
+    /// The KoMapedia user name of `owner`, if known.
+    pub(crate) fn user(&self, owner: &aktool::Owner) -> Option<String> {
+        match self.0.get(&owner.slug).or_else(|| self.0.get(&owner.name)) {
+            Some(user) => user_name(user),
+            None => user_page(&owner.link),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_log::test;
+
+    use super::Users;
+    use crate::model::aktool;
+
+    #[test]
+    fn mapping() {
+        let users = Users(toml::from_str(r#"al = "benutzer:Anna Lena""#).unwrap());
+        let owner = |owner: &str| serde_json::from_str::<aktool::Owner>(owner).unwrap();
+
+        assert_eq!(
+            users.user(&owner(
+                r#"{"id":1313,"name":"Anna|Lena","slug":"al","institution":"","link":"https://de.komapedia.org/wiki/Benutzer:Anna","event":16}"#
+            )),
+            Some("Anna_Lena".to_string())
+        );
+        assert_eq!(
+            users.user(&owner(
+                r#"{"id":1312,"name":"mmarx","slug":"mmarx","institution":"TU Dresden","link":"https://de.komapedia.org/wiki/Benutzer:Mmarx","event":16}"#
+            )),
+            Some("Mmarx".to_string())
+        );
+        assert_eq!(
+            users.user(&owner(
+                r#"{"id":1314,"name":"Gast","slug":"gast","institution":"","link":"https://example.org","event":16}"#
+            )),
+            None
+        );
+    }
+}